@@ -0,0 +1,87 @@
+// Benchmarks of the Box-linked AVLTree against the Rc<RefCell<..>> version it
+// replaced, which is kept in `rc_avltree` for this purpose.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use dsgym_rs::avltree::AVLTree;
+
+mod rc_avltree;
+
+const N: u32 = 10_000;
+
+// The even keys 0, 2, .., 2N - 2 in a scrambled order, so the tree is built by
+// random insertions, and every odd key falls between two stored ones.
+fn keys() -> Vec<u32> {
+    (0..N).map(|x| x.wrapping_mul(7919) % N * 2).collect()
+}
+
+fn add(c: &mut Criterion) {
+    let keys = keys();
+    let mut group = c.benchmark_group("avltree add 10k");
+    group.bench_function("Box", |b| {
+        b.iter_batched(
+            AVLTree::new,
+            |mut t| {
+                for &k in keys.iter() {
+                    t.add(k);
+                }
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("Rc<RefCell>", |b| {
+        b.iter_batched(
+            rc_avltree::AVLTree::new,
+            |mut t| {
+                for &k in keys.iter() {
+                    t.add(k);
+                }
+                t
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn contains(c: &mut Criterion) {
+    let keys = keys();
+    let mut t = AVLTree::new();
+    let mut rc_t = rc_avltree::AVLTree::new();
+    for &k in keys.iter() {
+        t.add(k);
+        rc_t.add(k);
+    }
+
+    let mut group = c.benchmark_group("avltree contains 10k hits");
+    group.bench_function("Box", |b| {
+        b.iter(|| keys.iter().filter(|&&k| t.contains(black_box(&k))).count())
+    });
+    group.bench_function("Rc<RefCell>", |b| {
+        b.iter(|| {
+            keys.iter()
+                .filter(|&&k| rc_t.contains(black_box(k)))
+                .count()
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("avltree contains 10k misses");
+    group.bench_function("Box", |b| {
+        b.iter(|| {
+            keys.iter()
+                .filter(|&&k| t.contains(black_box(&(k + 1))))
+                .count()
+        })
+    });
+    group.bench_function("Rc<RefCell>", |b| {
+        b.iter(|| {
+            keys.iter()
+                .filter(|&&k| rc_t.contains(black_box(k + 1)))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, add, contains);
+criterion_main!(benches);
@@ -0,0 +1,167 @@
+// The AVLTree as it was before its nodes moved into Box links, kept only so
+// that the benchmarks can compare the two. Trimmed to what they call.
+use std::cell::RefCell;
+use std::cmp::{max, Ordering};
+use std::rc::Rc;
+
+struct Node<T> {
+    key: T,
+    left: Link<T>,
+    right: Link<T>,
+    height: i32,
+}
+
+type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+
+pub struct AVLTree<T> {
+    root: Link<T>,
+}
+
+impl<T> Node<T> {
+    fn new(key: T) -> Link<T> {
+        Some(Rc::new(RefCell::new(Node {
+            key,
+            left: None,
+            right: None,
+            height: 1,
+        })))
+    }
+}
+
+impl<T: Ord> AVLTree<T> {
+    pub fn new() -> Self {
+        AVLTree { root: None }
+    }
+
+    // Adds key to the tree rooted at root, keeping an equal key already in
+    // the tree. Returns the new root and the key left out of the tree, if any.
+    fn add_inner(&self, mut root: Link<T>, key: T) -> (Link<T>, Option<T>) {
+        match root.take() {
+            Some(node) => {
+                let rejected = {
+                    let mut x = node.borrow_mut();
+                    match key.cmp(&x.key) {
+                        Ordering::Less => {
+                            let (left, rejected) = self.add_inner(x.left.take(), key);
+                            x.left = left;
+                            rejected
+                        }
+                        Ordering::Greater => {
+                            let (right, rejected) = self.add_inner(x.right.take(), key);
+                            x.right = right;
+                            rejected
+                        }
+                        Ordering::Equal => Some(key),
+                    }
+                };
+                if rejected.is_some() {
+                    return (Some(node), rejected);
+                }
+                Self::update_height(&node);
+                (Self::balance(Some(node)), None)
+            }
+            None => (Node::new(key), None),
+        }
+    }
+
+    fn rotate_left(mut root: Link<T>) -> Link<T> {
+        root.take().map(|x| {
+            let rch = x.borrow_mut().right.take().unwrap();
+            let rchlch = rch.borrow_mut().left.take();
+            {
+                let mut x = x.borrow_mut();
+                x.right = rchlch;
+                x.height = max(Self::tree_height(&x.left), Self::tree_height(&x.right)) + 1;
+            }
+            {
+                let mut y = rch.borrow_mut();
+                y.left = Some(x);
+                y.height = max(Self::tree_height(&y.left), Self::tree_height(&y.right)) + 1;
+            }
+            rch
+        })
+    }
+
+    fn rotate_right(mut root: Link<T>) -> Link<T> {
+        root.take().map(|x| {
+            let lch = x.borrow_mut().left.take().unwrap();
+            let lchrch = lch.borrow_mut().right.take();
+            {
+                let mut x = x.borrow_mut();
+                x.left = lchrch;
+                x.height = max(Self::tree_height(&x.left), Self::tree_height(&x.right)) + 1;
+            }
+            {
+                let mut y = lch.borrow_mut();
+                y.right = Some(x);
+                y.height = max(Self::tree_height(&y.left), Self::tree_height(&y.right)) + 1;
+            }
+            lch
+        })
+    }
+
+    fn tree_height(node: &Link<T>) -> i32 {
+        node.as_ref().map_or(0, |x| x.borrow().height)
+    }
+
+    fn balance_factor(node: &Link<T>) -> i32 {
+        node.as_ref().map_or(0, |x| {
+            let x = x.borrow();
+            Self::tree_height(&x.left) - Self::tree_height(&x.right)
+        })
+    }
+
+    fn balance(root: Link<T>) -> Link<T> {
+        let bf = Self::balance_factor(&root);
+        if (-1..=1).contains(&bf) {
+            return root;
+        }
+        let x = root?;
+        match bf {
+            -2 => {
+                let sub_bf = Self::balance_factor(&x.borrow().right);
+                if sub_bf > 0 {
+                    let mut x = x.borrow_mut();
+                    x.right = Self::rotate_right(x.right.take());
+                }
+                Self::rotate_left(Some(x))
+            }
+            2 => {
+                let sub_bf = Self::balance_factor(&x.borrow().left);
+                if sub_bf < 0 {
+                    let mut x = x.borrow_mut();
+                    x.left = Self::rotate_left(x.left.take());
+                }
+                Self::rotate_right(Some(x))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn update_height(node: &Rc<RefCell<Node<T>>>) {
+        let mut x = node.borrow_mut();
+        x.height = max(Self::tree_height(&x.left), Self::tree_height(&x.right)) + 1;
+    }
+
+    pub fn add(&mut self, key: T) -> bool {
+        let root = self.root.take();
+        let (root, rejected) = self.add_inner(root, key);
+        self.root = root;
+        rejected.is_none()
+    }
+
+    fn find(&self, root: &Link<T>, key: T) -> Link<T> {
+        root.as_ref().and_then(|node| {
+            let x = node.borrow();
+            match key.cmp(&x.key) {
+                Ordering::Less => self.find(&x.left, key),
+                Ordering::Greater => self.find(&x.right, key),
+                Ordering::Equal => Some(node.clone()),
+            }
+        })
+    }
+
+    pub fn contains(&self, key: T) -> bool {
+        self.find(&self.root, key).is_some()
+    }
+}
@@ -0,0 +1,2913 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::iter::FusedIterator;
+use std::mem::{replace, swap, take};
+use std::ops::{Bound, Index, RangeBounds};
+
+// The index of a missing node, playing the part of the null pointer.
+const NIL: u32 = u32::MAX;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Color {
+    Red,
+    Black,
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Left,
+    Right,
+}
+
+// The links of a node. Its key and value are kept at the same index of
+// `ArenaRBTreeMap::entries`, so that walking the tree never reads an entry.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    left: u32,
+    right: u32,
+    // the next vacant slot while the node is on the free list
+    parent: u32,
+    color: Color,
+    // number of nodes in the subtree rooted at this node
+    size: u32,
+}
+
+/// A red-black tree map with the same interface as [`RBTreeMap`], storing its
+/// nodes in an arena of `Vec`s and linking them by `u32` indices instead of pointers.
+///
+/// Slots of removed nodes are kept on a free list and reused by later insertions.
+/// [`shrink_to_fit`] compacts the nodes and releases the slots that are left over.
+///
+/// [`RBTreeMap`]: crate::rbtree::RBTreeMap
+/// [`shrink_to_fit`]: ArenaRBTreeMap::shrink_to_fit
+#[derive(Clone)]
+pub struct ArenaRBTreeMap<K, V> {
+    nodes: Vec<Node>,
+    // the entry of each node, `None` for a vacant slot
+    entries: Vec<Option<(K, V)>>,
+    // head of the list of vacant slots
+    free: u32,
+    root: u32,
+    size: usize,
+}
+
+// The root of a detached subtree and its black height, used by split and join.
+type Subtree = (u32, usize);
+
+// In-order traversal between two nodes following parent links, shared by the
+// map iterators. `front` and `back` are the next nodes to be visited from either
+// end; both are NIL once the two ends have met.
+struct RawIter {
+    front: u32,
+    back: u32,
+}
+
+// A traversal handing out the entries of the nodes it visits mutably. The links
+// are borrowed on their own, and the entries are split off the arena by slot up
+// front, each one taken out of `entries` when its node is visited.
+struct RawIterMut<'a, K: 'a, V: 'a> {
+    nodes: &'a [Node],
+    entries: Vec<Option<&'a mut (K, V)>>,
+    raw: RawIter,
+}
+
+// An iterator over the entries of a ArenaRBTreeMap.
+pub struct Iter<'a, K: 'a, V: 'a> {
+    map: &'a ArenaRBTreeMap<K, V>,
+    raw: RawIter,
+    length: usize,
+}
+
+// A mutable iterator over the entries of a ArenaRBTreeMap.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    inner: RawIterMut<'a, K, V>,
+    length: usize,
+}
+
+// An iterator over the keys of a ArenaRBTreeMap.
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+// An iterator over the values of a ArenaRBTreeMap.
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+// A mutable iterator over the values of a ArenaRBTreeMap.
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    inner: IterMut<'a, K, V>,
+}
+
+// An owning iterator over the entries of a ArenaRBTreeMap.
+// Entries are moved out of the arena as they are visited, leaving the links in place.
+pub struct IntoIter<K, V> {
+    map: ArenaRBTreeMap<K, V>,
+    raw: RawIter,
+    length: usize,
+}
+
+// An owning iterator over the keys of a ArenaRBTreeMap.
+pub struct IntoKeys<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+// An owning iterator over the values of a ArenaRBTreeMap.
+pub struct IntoValues<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+// An iterator over a sub-range of entries in a ArenaRBTreeMap.
+pub struct Range<'a, K: 'a, V: 'a> {
+    map: &'a ArenaRBTreeMap<K, V>,
+    raw: RawIter,
+}
+
+// A mutable iterator over a sub-range of entries in a ArenaRBTreeMap.
+pub struct RangeMut<'a, K: 'a, V: 'a> {
+    inner: RawIterMut<'a, K, V>,
+}
+
+/// An iterator produced by calling `extract_if` on ArenaRBTreeMap.
+pub struct ExtractIf<'a, K: 'a, V: 'a, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    map: &'a mut ArenaRBTreeMap<K, V>,
+    // the next node to be tested, NIL at the end
+    next: u32,
+    pred: F,
+}
+
+/// A view into a single entry in a map, which may either be vacant or occupied.
+///
+/// This `enum` is constructed from the [`entry`] method on [`ArenaRBTreeMap`].
+///
+/// [`entry`]: ArenaRBTreeMap::entry
+pub enum Entry<'a, K: 'a, V: 'a> {
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V>),
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+/// A view into a vacant entry in a `ArenaRBTreeMap`.
+/// It is part of the [`Entry`] enum.
+pub struct VacantEntry<'a, K: 'a, V: 'a> {
+    map: &'a mut ArenaRBTreeMap<K, V>,
+    key: K,
+    // the node under which the new node is linked, found by `entry`
+    parent: u32,
+    dir: Direction,
+}
+
+/// A view into an occupied entry in a `ArenaRBTreeMap`.
+/// It is part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, K: 'a, V: 'a> {
+    map: &'a mut ArenaRBTreeMap<K, V>,
+    node: u32,
+}
+
+/// A cursor over a `ArenaRBTreeMap`.
+///
+/// A `Cursor` is like an iterator, except that it can freely seek back-and-forth.
+///
+/// Cursors always point to an element in the tree, or to a "ghost" non-element that is
+/// logically located after the last element and before the first one.
+///
+/// Cursors are created with the [`ArenaRBTreeMap::lower_bound`] and
+/// [`ArenaRBTreeMap::upper_bound`] methods.
+pub struct Cursor<'a, K: 'a, V: 'a> {
+    // NIL for the ghost non-element
+    current: u32,
+    map: &'a ArenaRBTreeMap<K, V>,
+}
+
+/// A cursor over a `ArenaRBTreeMap` with editing operations.
+///
+/// A `CursorMut` is like an iterator, except that it can freely seek back-and-forth, and can
+/// safely mutate the tree during iteration. The nodes around the cursor are reached through
+/// their parent links, so moving, inserting and removing never search the tree from the root.
+///
+/// Cursors are created with the [`ArenaRBTreeMap::lower_bound_mut`] and
+/// [`ArenaRBTreeMap::upper_bound_mut`] methods.
+pub struct CursorMut<'a, K: 'a, V: 'a> {
+    // NIL for the ghost non-element
+    current: u32,
+    map: &'a mut ArenaRBTreeMap<K, V>,
+}
+
+impl Node {
+    fn new(color: Color) -> Node {
+        Node {
+            left: NIL,
+            right: NIL,
+            parent: NIL,
+            color,
+            size: 1,
+        }
+    }
+}
+
+impl<'a, K, V> ArenaRBTreeMap<K, V> {
+    /// Makes a new, empty `ArenaRBTreeMap`.
+    pub fn new() -> ArenaRBTreeMap<K, V> {
+        Self::with_capacity(0)
+    }
+
+    /// Makes a new, empty `ArenaRBTreeMap` with room for at least `capacity`
+    /// entries before the arena reallocates.
+    pub fn with_capacity(capacity: usize) -> ArenaRBTreeMap<K, V> {
+        ArenaRBTreeMap {
+            nodes: Vec::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            free: NIL,
+            root: NIL,
+            size: 0,
+        }
+    }
+
+    /// Returns the number of entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity().min(self.entries.capacity())
+    }
+
+    /// Compacts the arena and shrinks its capacity as much as possible.
+    ///
+    /// The nodes are moved into consecutive slots in key order, so that iterating
+    /// walks the arena from front to back, and the vacant slots left by removals
+    /// are released. This takes O(n) time.
+    pub fn shrink_to_fit(&mut self) {
+        let mut order = Vec::with_capacity(self.size);
+        let mut new_index = vec![NIL; self.nodes.len()];
+        let mut raw = RawIter::new(&self.nodes, self.root);
+        loop {
+            let x = raw.next_node(&self.nodes);
+            if x == NIL {
+                break;
+            }
+            new_index[x as usize] = order.len() as u32;
+            order.push(x);
+        }
+        let remap = |x: u32| if x == NIL { NIL } else { new_index[x as usize] };
+
+        let mut nodes: Vec<Node> = order
+            .iter()
+            .map(|&x| {
+                let node = self.nodes[x as usize];
+                Node {
+                    left: remap(node.left),
+                    right: remap(node.right),
+                    parent: remap(node.parent),
+                    ..node
+                }
+            })
+            .collect();
+        let mut entries: Vec<Option<(K, V)>> = order
+            .iter()
+            .map(|&x| self.entries[x as usize].take())
+            .collect();
+        nodes.shrink_to_fit();
+        entries.shrink_to_fit();
+        self.nodes = nodes;
+        self.entries = entries;
+        self.root = remap(self.root);
+        self.free = NIL;
+    }
+
+    /// Builds a map from entries sorted by key, in O(n) time.
+    ///
+    /// Consecutive entries with equal keys are deduplicated, keeping the last
+    /// one, like collecting into a map keeps the last value inserted for a key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key is less than the key before it.
+    pub fn from_sorted_iter<I>(iter: I) -> ArenaRBTreeMap<K, V>
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let iter = iter.into_iter();
+        let mut entries: Vec<(K, V)> = Vec::with_capacity(iter.size_hint().0);
+        for (key, value) in iter {
+            if let Some(last) = entries.last_mut() {
+                match last.0.cmp(&key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => {
+                        *last = (key, value);
+                        continue;
+                    }
+                    Ordering::Greater => panic!("keys must be sorted in ascending order"),
+                }
+            }
+            entries.push((key, value));
+        }
+        Self::build_sorted(entries)
+    }
+
+    /// Builds a map from a vector of entries sorted by key, in O(n) time.
+    ///
+    /// Consecutive entries with equal keys are deduplicated, keeping the last
+    /// one. When the keys are strictly ascending, the entries are moved into
+    /// the tree without an intermediate copy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key is less than the key before it.
+    pub fn from_sorted_vec(entries: Vec<(K, V)>) -> ArenaRBTreeMap<K, V>
+    where
+        K: Ord,
+    {
+        if entries.windows(2).all(|w| w[0].0 < w[1].0) {
+            Self::build_sorted(entries)
+        } else {
+            Self::from_sorted_iter(entries)
+        }
+    }
+
+    // Builds a perfectly balanced tree from entries with strictly ascending keys,
+    // colored as in `RBTreeMap::build_sorted`. The nodes are allocated in key
+    // order, so the arena starts out compact.
+    fn build_sorted(entries: Vec<(K, V)>) -> ArenaRBTreeMap<K, V> {
+        let n = entries.len();
+        let deepest = (usize::BITS - 1 - (n + 1).leading_zeros()) as usize;
+        let red_depth = if (n + 1).is_power_of_two() {
+            usize::MAX
+        } else {
+            deepest
+        };
+        let mut map = ArenaRBTreeMap::with_capacity(n);
+        let mut iter = entries.into_iter();
+        map.root = map.build_subtree(n, 0, red_depth, &mut iter);
+        map.size = n;
+        map
+    }
+
+    // Builds the subtree of the next n entries in order, with the root at depth.
+    fn build_subtree<I>(&mut self, n: usize, depth: usize, red_depth: usize, iter: &mut I) -> u32
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if n == 0 {
+            return NIL;
+        }
+        let n_left = (n - 1) / 2;
+        let left = self.build_subtree(n_left, depth + 1, red_depth, iter);
+        let (key, value) = iter.next().unwrap();
+        let color = if depth == red_depth {
+            Color::Red
+        } else {
+            Color::Black
+        };
+        let node = self.alloc(key, value, color);
+        let right = self.build_subtree(n - 1 - n_left, depth + 1, red_depth, iter);
+        self.node_mut(node).left = left;
+        self.node_mut(node).right = right;
+        for child in [left, right] {
+            if child != NIL {
+                self.node_mut(child).parent = node;
+            }
+        }
+        self.node_mut(node).size = n as u32;
+        node
+    }
+
+    /// Returns the number of elements in the map.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if the map contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Clears the map, removing all elements from the map.
+    /// The arena keeps its capacity.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.entries.clear();
+        self.free = NIL;
+        self.root = NIL;
+        self.size = 0;
+    }
+
+    /// Returns the value corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entry_of(self.search_node(key)).map(|(_, v)| v)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let node = self.search_node(key);
+        if node != NIL {
+            Some(&mut self.entry_mut(node).1)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key-value pair corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entry_of(self.search_node(key))
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.search_node(key) != NIL
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, `None` is returned.
+    ///
+    /// If the map did have this key present, the value is updated, and the old
+    /// value is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        K: Ord,
+    {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V>
+    where
+        K: Ord,
+    {
+        let mut cur = self.root;
+        let mut p = NIL;
+        let mut dir = Direction::Left;
+
+        while cur != NIL {
+            match key.cmp(self.key(cur)) {
+                Ordering::Less => {
+                    p = cur;
+                    cur = self.node(cur).left;
+                    dir = Direction::Left;
+                }
+                Ordering::Greater => {
+                    p = cur;
+                    cur = self.node(cur).right;
+                    dir = Direction::Right;
+                }
+                Ordering::Equal => {
+                    return Entry::Occupied(OccupiedEntry {
+                        map: self,
+                        node: cur,
+                    });
+                }
+            }
+        }
+        Entry::Vacant(VacantEntry {
+            map: self,
+            key,
+            parent: p,
+            dir,
+        })
+    }
+
+    // Links a new node as the `dir` child of `p`, which must be a free slot,
+    // and rebalances from there. A NIL `p` means the tree is empty.
+    fn insert_node(&mut self, p: u32, dir: Direction, key: K, value: V) -> u32 {
+        let new_node;
+        if p != NIL {
+            new_node = self.alloc(key, value, Color::Red);
+            match dir {
+                Direction::Left => self.node_mut(p).left = new_node,
+                Direction::Right => self.node_mut(p).right = new_node,
+            }
+            self.node_mut(new_node).parent = p;
+            let mut x = p;
+            while x != NIL {
+                let node = self.node_mut(x);
+                node.size += 1;
+                x = node.parent;
+            }
+            self.fix_after_insertion(new_node);
+        } else {
+            // empty tree case, set new root
+            new_node = self.alloc(key, value, Color::Black);
+            self.root = new_node;
+        }
+        self.size += 1;
+        new_node
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.remove_entry(key).map(|e| e.1)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the key
+    /// was previously in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let x = self.search_node(key);
+        if x == NIL {
+            return None;
+        }
+        Some(self.remove_node(x))
+    }
+
+    // Unlinks a node of this tree, rebalances, and frees its slot.
+    fn remove_node(&mut self, x: u32) -> (K, V) {
+        self.unlink_node(x);
+        self.dealloc(x)
+    }
+
+    // Unlinks a node of this tree and rebalances. The node is left detached
+    // with NIL links, to be freed or linked again by the caller.
+    fn unlink_node(&mut self, x: u32) {
+        self.size -= 1;
+
+        if self.node(x).left != NIL && self.node(x).right != NIL {
+            // move x down to the position of its successor, which has no left
+            // child, and then remove x from there
+            let y = self.first(self.node(x).right);
+            self.swap_with_successor(x, y);
+        }
+
+        // x is about to be unlinked, take it out of the subtree sizes above it.
+        // A leaf stays in place during fix_after_deletion, so it counts as empty.
+        self.node_mut(x).size = 0;
+        let mut p = self.node(x).parent;
+        while p != NIL {
+            let node = self.node_mut(p);
+            node.size -= 1;
+            p = node.parent;
+        }
+
+        let (l, r) = (self.node(x).left, self.node(x).right);
+        if l != NIL || r != NIL {
+            // x has only 1 child: replace and return
+            // in this case, replacement must be red
+            let replacement = if l != NIL { l } else { r };
+            let p = self.node(x).parent;
+            self.node_mut(replacement).parent = p;
+            self.replace_child(p, x, replacement);
+            self.set_color(replacement, Color::Black);
+        } else {
+            // x is leaf: remove and fix
+            if !self.is_red(x) {
+                self.fix_after_deletion(x);
+            }
+            let p = self.node(x).parent;
+            self.replace_child(p, x, NIL);
+        }
+        self.set_color(self.root, Color::Black);
+
+        let node = self.node_mut(x);
+        node.left = NIL;
+        node.right = NIL;
+        node.parent = NIL;
+        node.size = 1;
+    }
+
+    // Exchanges the positions, colors and subtree sizes of x and its successor y
+    // in the tree, relinking the nodes so that the indices held by iterators and
+    // cursors keep referring to the same entries.
+    // Assumes x has two children, so y is the leftmost node of x.right.
+    fn swap_with_successor(&mut self, x: u32, y: u32) {
+        let (p, xl, xr) = (self.node(x).parent, self.node(x).left, self.node(x).right);
+        let (yp, yr) = (self.node(y).parent, self.node(y).right);
+
+        self.replace_child(p, x, y);
+        self.node_mut(y).parent = p;
+
+        self.node_mut(y).left = xl;
+        self.node_mut(xl).parent = y;
+
+        if y == xr {
+            //     x            y
+            //    / \          / \
+            //   xl  y   =>   xl  x
+            //        \            \
+            //        yr           yr
+            self.node_mut(y).right = x;
+            self.node_mut(x).parent = y;
+        } else {
+            self.node_mut(y).right = xr;
+            self.node_mut(xr).parent = y;
+            self.node_mut(yp).left = x;
+            self.node_mut(x).parent = yp;
+        }
+
+        self.node_mut(x).left = NIL;
+        self.node_mut(x).right = yr;
+        if yr != NIL {
+            self.node_mut(yr).parent = x;
+        }
+        self.swap_color_and_size(x, y);
+    }
+
+    /// Gets an iterator over the entries of the map, sorted by key.
+    pub fn iter(&'a self) -> Iter<'a, K, V> {
+        Iter {
+            map: self,
+            raw: RawIter::new(&self.nodes, self.root),
+            length: self.size,
+        }
+    }
+
+    /// Gets a mutable iterator over the entries of the map, sorted by key.
+    pub fn iter_mut(&'a mut self) -> IterMut<'a, K, V> {
+        let raw = RawIter::new(&self.nodes, self.root);
+        IterMut {
+            length: self.size,
+            inner: RawIterMut::new(self, raw),
+        }
+    }
+
+    /// Gets an iterator over the keys of the map, in sorted order.
+    pub fn keys(&'a self) -> Keys<'a, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Gets an iterator over the values of the map, in order by key.
+    pub fn values(&'a self) -> Values<'a, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// Gets a mutable iterator over the values of the map, in order by key.
+    pub fn values_mut(&'a mut self) -> ValuesMut<'a, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the keys, in sorted order.
+    /// The map cannot be used after calling this.
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the values, in order by key.
+    /// The map cannot be used after calling this.
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Constructs a double-ended iterator over a sub-range of elements in the map.
+    ///
+    /// The simplest way is to use the range syntax `min..max`, thus `range(min..max)` will
+    /// yield elements from min (inclusive) to max (exclusive). `range((Excluded(4), Included(10)))`
+    /// will yield a left-exclusive, right-inclusive range from 4 to 10.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn range<T, R>(&'a self, range: R) -> Range<'a, K, V>
+    where
+        K: Borrow<T> + Ord,
+        T: ?Sized + Ord,
+        R: RangeBounds<T>,
+    {
+        Range {
+            map: self,
+            raw: self.raw_range(range),
+        }
+    }
+
+    /// Constructs a mutable double-ended iterator over a sub-range of elements in the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn range_mut<T, R>(&'a mut self, range: R) -> RangeMut<'a, K, V>
+    where
+        K: Borrow<T> + Ord,
+        T: ?Sized + Ord,
+        R: RangeBounds<T>,
+    {
+        let raw = self.raw_range(range);
+        RangeMut {
+            inner: RawIterMut::new(self, raw),
+        }
+    }
+
+    // Finds the first and the last node inside the range.
+    fn raw_range<T, R>(&self, range: R) -> RawIter
+    where
+        K: Borrow<T> + Ord,
+        T: ?Sized + Ord,
+        R: RangeBounds<T>,
+    {
+        Self::check_range(&range);
+
+        let front = self.first_after(|k| match range.start_bound() {
+            Bound::Included(s) => k.borrow() < s,
+            Bound::Excluded(s) => k.borrow() <= s,
+            Bound::Unbounded => false,
+        });
+        let back = self.last_while(|k| match range.end_bound() {
+            Bound::Included(e) => k.borrow() <= e,
+            Bound::Excluded(e) => k.borrow() < e,
+            Bound::Unbounded => true,
+        });
+        if front == NIL || back == NIL || self.key(front) > self.key(back) {
+            // no key lies inside the range
+            return RawIter {
+                front: NIL,
+                back: NIL,
+            };
+        }
+        RawIter { front, back }
+    }
+
+    // Returns the last node whose key satisfies `pred`, assuming it holds for
+    // a prefix of the keys in order, or NIL when it holds for none.
+    fn last_while<F>(&self, pred: F) -> u32
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut found = NIL;
+        let mut x = self.root;
+        while x != NIL {
+            if pred(self.key(x)) {
+                found = x;
+                x = self.node(x).right;
+            } else {
+                x = self.node(x).left;
+            }
+        }
+        found
+    }
+
+    // Returns the first node whose key does not satisfy `pred`, assuming it
+    // holds for a prefix of the keys in order, or NIL when it holds for all.
+    fn first_after<F>(&self, pred: F) -> u32
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut found = NIL;
+        let mut x = self.root;
+        while x != NIL {
+            if pred(self.key(x)) {
+                x = self.node(x).right;
+            } else {
+                found = x;
+                x = self.node(x).left;
+            }
+        }
+        found
+    }
+
+    // Borrows the key and value of a node, or returns `None` for NIL.
+    fn entry_of(&self, x: u32) -> Option<(&K, &V)> {
+        if x == NIL {
+            return None;
+        }
+        self.entries[x as usize].as_ref().map(|(k, v)| (k, v))
+    }
+
+    fn check_range<T, R>(range: &R)
+    where
+        T: ?Sized + Ord,
+        R: RangeBounds<T>,
+    {
+        match (range.start_bound(), range.end_bound()) {
+            (Bound::Excluded(s), Bound::Excluded(e)) if s == e => {
+                panic!("range start and end are equal and excluded in ArenaRBTreeMap")
+            }
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e))
+                if s > e =>
+            {
+                panic!("range start is greater than range end in ArenaRBTreeMap")
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the first key-value pair in the map.
+    /// The key in this pair is the minimum key in the map.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.entry_of(self.first(self.root))
+    }
+
+    /// Returns the last key-value pair in the map.
+    /// The key in this pair is the maximum key in the map.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.entry_of(self.last(self.root))
+    }
+
+    /// Removes and returns the first element in the map.
+    /// The key of this element is the minimum key that was in the map.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        if self.root == NIL {
+            return None;
+        }
+        Some(self.remove_node(self.first(self.root)))
+    }
+
+    /// Removes and returns the last element in the map.
+    /// The key of this element is the maximum key that was in the map.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        if self.root == NIL {
+            return None;
+        }
+        Some(self.remove_node(self.last(self.root)))
+    }
+
+    /// Returns the key-value pair with the greatest key less than or equal to `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entry_of(self.last_while(|k| k.borrow() <= key))
+    }
+
+    /// Returns the key-value pair with the least key greater than or equal to `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entry_of(self.first_after(|k| k.borrow() < key))
+    }
+
+    /// Returns the key-value pair with the greatest key strictly less than `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn lower<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entry_of(self.last_while(|k| k.borrow() < key))
+    }
+
+    /// Returns the key-value pair with the least key strictly greater than `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn higher<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.entry_of(self.first_after(|k| k.borrow() <= key))
+    }
+
+    /// Splits the collection into two at the given key. Returns everything after the given key,
+    /// including the key.
+    ///
+    /// The tree is cut along the search path of `key` in O(log n) time, and the
+    /// nodes of the smaller part are then moved into an arena of their own.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let bh = self.black_height(self.root);
+        let ((left, _), (right, _)) = self.split(self.root, bh, key);
+        let right_size = self.size_of(right) as usize;
+        let left_size = self.size - right_size;
+
+        let mut other = ArenaRBTreeMap::with_capacity(left_size.min(right_size));
+        if right_size <= left_size {
+            other.root = Self::move_nodes(self, &mut other, right);
+            self.root = left;
+        } else {
+            other.root = Self::move_nodes(self, &mut other, left);
+            self.root = right;
+            swap(self, &mut other);
+        }
+        self.size = left_size;
+        other.size = right_size;
+        other
+    }
+
+    /// Moves all elements from `other` into `self`, leaving `other` empty.
+    ///
+    /// If a key from `other` is already present in `self`, the respective
+    /// value from `self` will be overwritten with the respective value from `other`.
+    ///
+    /// When all keys of one map are less than all keys of the other, the two trees
+    /// are joined as by [`join`]. Otherwise the entries of `other` are inserted
+    /// one by one.
+    ///
+    /// [`join`]: ArenaRBTreeMap::join
+    pub fn append(&mut self, other: &mut Self)
+    where
+        K: Ord,
+    {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            swap(self, other);
+            return;
+        }
+
+        let self_first = self.first_key_value().unwrap().0;
+        let self_last = self.last_key_value().unwrap().0;
+        let other_first = other.first_key_value().unwrap().0;
+        let other_last = other.last_key_value().unwrap().0;
+        if self_last < other_first {
+            let (key, value) = other.pop_first().unwrap();
+            *self = Self::join(take(self), key, value, take(other));
+        } else if other_last < self_first {
+            let (key, value) = other.pop_last().unwrap();
+            *self = Self::join(take(other), key, value, take(self));
+        } else {
+            for (key, value) in take(other) {
+                self.insert(key, value);
+            }
+        }
+    }
+
+    /// Concatenates `left`, the entry `key`/`value` and `right` into a single map.
+    ///
+    /// The nodes of the smaller map are moved into the arena of the larger one,
+    /// and the trees are joined at the height where their black heights match.
+    /// This takes O(min(m, n) + log(m + n)) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some key of `left` is not less than `key`,
+    /// or some key of `right` is not greater than `key`.
+    pub fn join(left: Self, key: K, value: V, right: Self) -> Self
+    where
+        K: Ord,
+    {
+        if let Some((k, _)) = left.last_key_value() {
+            assert!(*k < key, "keys of the left map must be less than the pivot");
+        }
+        if let Some((k, _)) = right.first_key_value() {
+            assert!(
+                *k > key,
+                "keys of the right map must be greater than the pivot"
+            );
+        }
+
+        let left_is_larger = left.len() >= right.len();
+        let (mut map, mut other) = if left_is_larger {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        let other_root = other.root;
+        let moved = Self::move_nodes(&mut other, &mut map, other_root);
+        let (l, r) = if left_is_larger {
+            (map.root, moved)
+        } else {
+            (moved, map.root)
+        };
+
+        let pivot = map.alloc(key, value, Color::Black);
+        let bl = map.black_height(l);
+        let br = map.black_height(r);
+        let (root, _) = map.join_nodes(l, bl, pivot, r, br);
+        map.root = root;
+        map.size += other.size + 1;
+        map
+    }
+
+    // Joins two detached trees with black roots and black heights bl and br,
+    // and a detached pivot node whose key lies between them, as in
+    // `RBTreeMap::join_nodes`.
+    // Returns the root of the joined tree and its black height.
+    fn join_nodes(&mut self, left: u32, bl: usize, pivot: u32, right: u32, br: usize) -> Subtree {
+        if bl == br {
+            let node = self.node_mut(pivot);
+            node.left = left;
+            node.right = right;
+            node.parent = NIL;
+            node.color = Color::Black;
+            for child in [left, right] {
+                if child != NIL {
+                    self.node_mut(child).parent = pivot;
+                }
+            }
+            self.update_size(pivot);
+            return (pivot, bl + 1);
+        }
+
+        let (taller, h_taller, shorter) = if bl > br {
+            (left, bl, right)
+        } else {
+            (right, br, left)
+        };
+        let h_shorter = bl.min(br);
+
+        // find c, the black node on the facing spine with the same black height
+        // as the shorter tree, c may be NIL
+        let mut c = taller;
+        let mut p = NIL;
+        let mut h = h_taller;
+        while h != h_shorter || self.is_red(c) {
+            if !self.is_red(c) {
+                h -= 1;
+            }
+            p = c;
+            c = if bl > br {
+                self.node(c).right
+            } else {
+                self.node(c).left
+            };
+        }
+
+        //        p                p
+        //         \                \
+        //          c     =>        pivot
+        //                          /   \
+        //                         c   shorter
+        // (mirrored when the right tree is taller)
+        if bl > br {
+            self.node_mut(p).right = pivot;
+            self.node_mut(pivot).left = c;
+            self.node_mut(pivot).right = shorter;
+        } else {
+            self.node_mut(p).left = pivot;
+            self.node_mut(pivot).left = shorter;
+            self.node_mut(pivot).right = c;
+        }
+        self.node_mut(pivot).parent = p;
+        for child in [c, shorter] {
+            if child != NIL {
+                self.node_mut(child).parent = pivot;
+            }
+        }
+        self.update_size(pivot);
+        let added = self.size_of(shorter) + 1;
+        let mut x = p;
+        while x != NIL {
+            let node = self.node_mut(x);
+            node.size += added;
+            x = node.parent;
+        }
+
+        // fix the taller tree up as if it was the whole tree
+        let root = replace(&mut self.root, taller);
+        let grew = self.fix_after_insertion(pivot);
+        let joined = replace(&mut self.root, root);
+        (joined, h_taller + grew as usize)
+    }
+
+    // Splits a detached tree with a black root and black height bh into the
+    // trees of the keys less than `key` and of the keys not less than `key`,
+    // returned with their black heights, as in `RBTreeMap::split`.
+    fn split<Q>(&mut self, node: u32, bh: usize, key: &Q) -> (Subtree, Subtree)
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        if node == NIL {
+            return ((NIL, 0), (NIL, 0));
+        }
+
+        // detach both children, painting them black to make them proper trees
+        let mut children = [(self.node(node).left, 0), (self.node(node).right, 0)];
+        for (child, h) in children.iter_mut() {
+            *h = bh - 1;
+            if *child != NIL {
+                self.node_mut(*child).parent = NIL;
+                if self.is_red(*child) {
+                    self.node_mut(*child).color = Color::Black;
+                    *h += 1;
+                }
+            }
+        }
+        let [(l, bl), (r, br)] = children;
+        let n = self.node_mut(node);
+        n.left = NIL;
+        n.right = NIL;
+        n.size = 1;
+
+        if key <= self.key(node).borrow() {
+            let (less, (ge, bge)) = self.split(l, bl, key);
+            (less, self.join_nodes(ge, bge, node, r, br))
+        } else {
+            let ((less, bless), ge) = self.split(r, br, key);
+            (self.join_nodes(l, bl, node, less, bless), ge)
+        }
+    }
+
+    // Moves the detached subtree rooted at `root` from one arena into another,
+    // keeping its shape, colors and sizes, and returns its new root.
+    fn move_nodes(from: &mut Self, to: &mut Self, root: u32) -> u32 {
+        let mut new_root = NIL;
+        let mut stack = Vec::new();
+        if root != NIL {
+            stack.push((root, NIL, Direction::Left));
+        }
+        while let Some((x, p, dir)) = stack.pop() {
+            let node = *from.node(x);
+            let (key, value) = from.dealloc(x);
+            let y = to.alloc(key, value, node.color);
+            to.node_mut(y).parent = p;
+            to.node_mut(y).size = node.size;
+            let (left, right) = (node.left, node.right);
+            if p == NIL {
+                new_root = y;
+            } else {
+                match dir {
+                    Direction::Left => to.node_mut(p).left = y,
+                    Direction::Right => to.node_mut(p).right = y,
+                }
+            }
+            if left != NIL {
+                stack.push((left, y, Direction::Left));
+            }
+            if right != NIL {
+                stack.push((right, y, Direction::Right));
+            }
+        }
+        new_root
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, remove all pairs `(k, v)` for which `f(&k, &mut v)` returns `false`.
+    /// The elements are visited in ascending key order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Creates an iterator that visits all elements in ascending key order and
+    /// uses a closure to determine if an element should be removed.
+    ///
+    /// If the closure returns `true`, the element is removed from the map and
+    /// yielded. If the closure returns `false`, or panics, the element remains
+    /// in the map and will not be yielded.
+    ///
+    /// Each element is removed with a complete rebalancing step before it is
+    /// yielded, so the tree stays valid if the closure panics or the iterator
+    /// is dropped before it is exhausted; the remaining elements are then kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let next = self.first(self.root);
+        ExtractIf {
+            map: self,
+            next,
+            pred,
+        }
+    }
+
+    /// Returns a [`Cursor`] pointing at the first element that is above the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the first
+    /// element of the map.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        Cursor {
+            current: self.lower_bound_node(bound),
+            map: self,
+        }
+    }
+
+    /// Returns a [`CursorMut`] pointing at the first element that is above the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the first
+    /// element of the map.
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        CursorMut {
+            current: self.lower_bound_node(bound),
+            map: self,
+        }
+    }
+
+    /// Returns a [`Cursor`] pointing at the last element that is below the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the last
+    /// element of the map.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        Cursor {
+            current: self.upper_bound_node(bound),
+            map: self,
+        }
+    }
+
+    /// Returns a [`CursorMut`] pointing at the last element that is below the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the last
+    /// element of the map.
+    pub fn upper_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        CursorMut {
+            current: self.upper_bound_node(bound),
+            map: self,
+        }
+    }
+
+    fn lower_bound_node<Q>(&self, bound: Bound<&Q>) -> u32
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        match bound {
+            Bound::Included(key) => self.first_after(|k| k.borrow() < key),
+            Bound::Excluded(key) => self.first_after(|k| k.borrow() <= key),
+            Bound::Unbounded => self.first(self.root),
+        }
+    }
+
+    fn upper_bound_node<Q>(&self, bound: Bound<&Q>) -> u32
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        match bound {
+            Bound::Included(key) => self.last_while(|k| k.borrow() <= key),
+            Bound::Excluded(key) => self.last_while(|k| k.borrow() < key),
+            Bound::Unbounded => self.last(self.root),
+        }
+    }
+
+    /// Returns the key-value pair with the `k`-th smallest key (counting from zero),
+    /// or `None` if `k >= self.len()`.
+    pub fn select(&self, k: usize) -> Option<(&K, &V)> {
+        let mut k = k;
+        let mut x = self.root;
+        while x != NIL {
+            let node = self.node(x);
+            let ls = self.size_of(node.left) as usize;
+            match k.cmp(&ls) {
+                Ordering::Less => x = node.left,
+                Ordering::Equal => return self.entry_of(x),
+                Ordering::Greater => {
+                    k -= ls + 1;
+                    x = node.right;
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of keys in the map that are less than `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.count_while(|k| k.borrow() < key)
+    }
+
+    /// Returns the number of elements in a sub-range of the map, without visiting them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn count_in_range<T, R>(&self, range: R) -> usize
+    where
+        K: Borrow<T>,
+        T: ?Sized + Ord,
+        R: RangeBounds<T>,
+    {
+        Self::check_range(&range);
+
+        let before_start = match range.start_bound() {
+            Bound::Included(s) => self.count_while(|k| k.borrow() < s),
+            Bound::Excluded(s) => self.count_while(|k| k.borrow() <= s),
+            Bound::Unbounded => 0,
+        };
+        let until_end = match range.end_bound() {
+            Bound::Included(e) => self.count_while(|k| k.borrow() <= e),
+            Bound::Excluded(e) => self.count_while(|k| k.borrow() < e),
+            Bound::Unbounded => self.size,
+        };
+        until_end.saturating_sub(before_start)
+    }
+
+    // Counts the keys for which `pred` holds, assuming it holds for a prefix
+    // of the keys in order, in a single descent using the subtree sizes.
+    fn count_while<F>(&self, pred: F) -> usize
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut count = 0;
+        let mut x = self.root;
+        while x != NIL {
+            let node = self.node(x);
+            if pred(self.key(x)) {
+                count += self.size_of(node.left) as usize + 1;
+                x = node.right;
+            } else {
+                x = node.left;
+            }
+        }
+        count
+    }
+
+    fn search_node<Q>(&self, key: &Q) -> u32
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let mut cur = self.root;
+        while cur != NIL {
+            match key.cmp(self.key(cur).borrow()) {
+                Ordering::Less => cur = self.node(cur).left,
+                Ordering::Greater => cur = self.node(cur).right,
+                Ordering::Equal => return cur,
+            }
+        }
+        NIL
+    }
+
+    // Stores a new node in a vacant slot, or at the end of the arena when there is none.
+    fn alloc(&mut self, key: K, value: V, color: Color) -> u32 {
+        if self.free != NIL {
+            let x = self.free;
+            self.free = self.node(x).parent;
+            self.nodes[x as usize] = Node::new(color);
+            self.entries[x as usize] = Some((key, value));
+            x
+        } else {
+            assert!(
+                self.nodes.len() < NIL as usize,
+                "ArenaRBTreeMap cannot hold more than u32::MAX - 1 entries"
+            );
+            self.nodes.push(Node::new(color));
+            self.entries.push(Some((key, value)));
+            (self.nodes.len() - 1) as u32
+        }
+    }
+
+    // Moves the entry out of a slot and puts the slot on the free list.
+    fn dealloc(&mut self, x: u32) -> (K, V) {
+        let entry = self.entries[x as usize]
+            .take()
+            .expect("link to a vacant slot");
+        self.node_mut(x).parent = self.free;
+        self.free = x;
+        entry
+    }
+
+    fn node(&self, x: u32) -> &Node {
+        &self.nodes[x as usize]
+    }
+
+    fn node_mut(&mut self, x: u32) -> &mut Node {
+        &mut self.nodes[x as usize]
+    }
+
+    fn key(&self, x: u32) -> &K {
+        &self.entries[x as usize]
+            .as_ref()
+            .expect("link to a vacant slot")
+            .0
+    }
+
+    fn entry_mut(&mut self, x: u32) -> &mut (K, V) {
+        self.entries[x as usize]
+            .as_mut()
+            .expect("link to a vacant slot")
+    }
+
+    fn left_of(&self, x: u32) -> u32 {
+        if x != NIL {
+            self.node(x).left
+        } else {
+            NIL
+        }
+    }
+
+    fn right_of(&self, x: u32) -> u32 {
+        if x != NIL {
+            self.node(x).right
+        } else {
+            NIL
+        }
+    }
+
+    fn parent_of(&self, x: u32) -> u32 {
+        if x != NIL {
+            self.node(x).parent
+        } else {
+            NIL
+        }
+    }
+
+    fn set_color(&mut self, x: u32, color: Color) {
+        if x != NIL {
+            self.node_mut(x).color = color;
+        }
+    }
+
+    fn is_red(&self, x: u32) -> bool {
+        x != NIL && self.node(x).color == Color::Red
+    }
+
+    // Returns the number of black nodes on a path from x down to a leaf,
+    // including x itself.
+    fn black_height(&self, x: u32) -> usize {
+        let mut h = 0;
+        let mut x = x;
+        while x != NIL {
+            if !self.is_red(x) {
+                h += 1;
+            }
+            x = self.node(x).left;
+        }
+        h
+    }
+
+    fn size_of(&self, x: u32) -> u32 {
+        if x != NIL {
+            self.node(x).size
+        } else {
+            0
+        }
+    }
+
+    // Recomputes the subtree size of x from its children.
+    fn update_size(&mut self, x: u32) {
+        let node = self.node(x);
+        let size = self.size_of(node.left) + self.size_of(node.right) + 1;
+        self.node_mut(x).size = size;
+    }
+
+    fn swap_color_and_size(&mut self, x: u32, y: u32) {
+        let (xc, xs) = (self.node(x).color, self.node(x).size);
+        let (yc, ys) = (self.node(y).color, self.node(y).size);
+        let node = self.node_mut(x);
+        node.color = yc;
+        node.size = ys;
+        let node = self.node_mut(y);
+        node.color = xc;
+        node.size = xs;
+    }
+
+    // Makes `new` the child of p in place of `old`, or the root when p is NIL.
+    fn replace_child(&mut self, p: u32, old: u32, new: u32) {
+        if p != NIL {
+            let node = self.node_mut(p);
+            if node.left == old {
+                node.left = new;
+            } else {
+                node.right = new;
+            }
+        } else {
+            self.root = new;
+        }
+    }
+
+    fn first(&self, x: u32) -> u32 {
+        first(&self.nodes, x)
+    }
+
+    fn last(&self, x: u32) -> u32 {
+        last(&self.nodes, x)
+    }
+
+    fn successor(&self, x: u32) -> u32 {
+        successor(&self.nodes, x)
+    }
+
+    fn predecessor(&self, x: u32) -> u32 {
+        predecessor(&self.nodes, x)
+    }
+
+    // Returns the node after x in key order, where NIL is the ghost
+    // non-element between the last node and the first one.
+    fn next_of(&self, x: u32) -> u32 {
+        if x == NIL {
+            self.first(self.root)
+        } else {
+            self.successor(x)
+        }
+    }
+
+    // Returns the node before x in key order, see `next_of`.
+    fn prev_of(&self, x: u32) -> u32 {
+        if x == NIL {
+            self.last(self.root)
+        } else {
+            self.predecessor(x)
+        }
+    }
+
+    // Assumes x and its right child are not NIL
+    // Rotation here exchanges their colors.
+    // When x.right is red, it will not violate rules of Red-Black tree.
+    fn rotate_left(&mut self, x: u32) {
+        let y = self.right_of(x);
+        assert!(y != NIL);
+
+        let p = self.node(x).parent;
+        let ly = self.node(y).left;
+
+        self.node_mut(x).right = ly;
+        if ly != NIL {
+            self.node_mut(ly).parent = x;
+        }
+        self.node_mut(y).left = x;
+        self.node_mut(y).parent = p;
+        self.node_mut(x).parent = y;
+        self.swap_color_and_size(x, y);
+        self.update_size(x);
+        self.replace_child(p, x, y);
+    }
+
+    // Assumes x and its left child are not NIL
+    // Rotation here exchanges their colors.
+    // When x.left is red, it will not violate rules of Red-Black tree.
+    fn rotate_right(&mut self, x: u32) {
+        let y = self.left_of(x);
+        assert!(y != NIL);
+
+        let p = self.node(x).parent;
+        let ry = self.node(y).right;
+
+        self.node_mut(x).left = ry;
+        if ry != NIL {
+            self.node_mut(ry).parent = x;
+        }
+        self.node_mut(y).right = x;
+        self.node_mut(y).parent = p;
+        self.node_mut(x).parent = y;
+        self.swap_color_and_size(x, y);
+        self.update_size(x);
+        self.replace_child(p, x, y);
+    }
+
+    // Returns `true` when the color flips reach the root,
+    // which means the black height of the tree has grown by one.
+    fn fix_after_insertion(&mut self, node: u32) -> bool {
+        if node == NIL {
+            return false;
+        }
+        let mut x = node;
+        self.set_color(x, Color::Red);
+
+        while x != NIL && self.root != x && self.is_red(self.parent_of(x)) {
+            let p = self.parent_of(x);
+            let g = self.parent_of(p);
+
+            if p == self.left_of(g) {
+                //      g
+                //     / \
+                //    p   u
+                //    |
+                //    x
+                // u and g may be NIL
+                let u = self.right_of(g);
+                if self.is_red(u) {
+                    self.set_color(p, Color::Black);
+                    self.set_color(u, Color::Black);
+                    self.set_color(g, Color::Red);
+                    x = g;
+                } else {
+                    //      g              g
+                    //     / \            / \
+                    //    p   u          x   u
+                    //     \            /
+                    //      x    ==>   p
+                    if x == self.right_of(p) {
+                        self.rotate_left(p);
+                        x = p;
+                    }
+                    //      g             p
+                    //     / \           / \
+                    //    p   u         x   g
+                    //   /                   \
+                    //  x       ==>           u
+                    self.rotate_right(g);
+                }
+            } else {
+                // Symmetric case
+                let u = self.left_of(g);
+                if self.is_red(u) {
+                    self.set_color(p, Color::Black);
+                    self.set_color(u, Color::Black);
+                    self.set_color(g, Color::Red);
+                    x = g;
+                } else {
+                    if x == self.left_of(p) {
+                        self.rotate_right(p);
+                        x = p;
+                    }
+                    self.rotate_left(g);
+                }
+            }
+        }
+        let grew = self.is_red(self.root);
+        self.set_color(self.root, Color::Black);
+        grew
+    }
+
+    fn fix_after_deletion(&mut self, node: u32) {
+        let mut x = node;
+
+        while x != self.root && !self.is_red(x) {
+            let p = self.parent_of(x);
+            if x == self.left_of(p) {
+                let mut y = self.right_of(p);
+
+                if self.is_red(y) {
+                    self.rotate_left(p);
+                    y = self.right_of(p);
+                }
+
+                if !self.is_red(self.left_of(y)) && !self.is_red(self.right_of(y)) {
+                    // no red links to take, subtract 1 black depth from sibling,
+                    // try to increase black depth of parent node in next iteration
+                    self.set_color(y, Color::Red);
+                    x = p;
+                } else {
+                    // at least one of sibling's children is red, take this link
+                    if self.is_red(self.left_of(y)) {
+                        self.rotate_right(y);
+                        y = self.parent_of(y);
+                    }
+
+                    //          p              y
+                    //         / \            / \
+                    //        x   y          p  ry
+                    //           / \        / \
+                    //          ly ry  =>  x  ly
+                    // dist   1  2  1      2  2  1
+                    // dist: number of black links to root of this subtree
+                    self.rotate_left(p);
+                    self.set_color(self.right_of(y), Color::Black);
+                    x = self.root;
+                }
+            } else {
+                // symmetric case: x is right child of p
+                let mut y = self.left_of(p);
+                if self.is_red(y) {
+                    self.rotate_right(p);
+                    y = self.left_of(p);
+                }
+
+                if !self.is_red(self.left_of(y)) && !self.is_red(self.right_of(y)) {
+                    // no red links to take, subtract 1 black depth from sibling,
+                    // try to increase black depth of parent node in next iteration
+                    self.set_color(y, Color::Red);
+                    x = p;
+                } else {
+                    // at least one of sibling's children is red, take this link
+                    if self.is_red(self.right_of(y)) {
+                        self.rotate_left(y);
+                        y = self.parent_of(y);
+                    }
+                    self.rotate_right(p);
+                    self.set_color(self.left_of(y), Color::Black);
+                    x = self.root;
+                }
+            }
+        }
+
+        self.set_color(x, Color::Black);
+    }
+
+    /// check if the subtree sizes and parent links under x are consistent,
+    /// returns the size of the tree rooted at x
+    #[cfg(test)]
+    fn check_links(&self, x: u32) -> Option<usize> {
+        if x == NIL {
+            return Some(0);
+        }
+        let node = self.node(x);
+        for child in [node.left, node.right] {
+            if child != NIL && self.node(child).parent != x {
+                return None;
+            }
+        }
+        self.entries[x as usize].as_ref()?;
+        let size = self.check_links(node.left)? + self.check_links(node.right)? + 1;
+        if size != node.size as usize {
+            return None;
+        }
+        Some(size)
+    }
+
+    /// check if a tree rooted at x is a 2-3-4 tree,
+    /// when it's 2-3-4 tree, returns the black height of root,
+    /// when it's not, returns `None`
+    #[cfg(test)]
+    fn is_234tree(&self, x: u32) -> Option<usize> {
+        if x == NIL {
+            return Some(0);
+        }
+        let bh_left = self.is_234tree(self.node(x).left)?;
+        let bh_right = self.is_234tree(self.node(x).right)?;
+        if bh_left != bh_right {
+            return None;
+        }
+        if self.is_red(x) {
+            Some(bh_left)
+        } else {
+            Some(bh_left + 1)
+        }
+    }
+
+    /// count the slots on the free list, returns `None` if it runs into an
+    /// occupied slot or a cycle
+    #[cfg(test)]
+    fn count_free(&self) -> Option<usize> {
+        let mut count = 0;
+        let mut x = self.free;
+        while x != NIL {
+            if self.entries.get(x as usize)?.is_some() || count == self.nodes.len() {
+                return None;
+            }
+            count += 1;
+            x = self.node(x).parent;
+        }
+        Some(count)
+    }
+}
+
+// Returns the leftmost node of the subtree rooted at x.
+fn first(nodes: &[Node], x: u32) -> u32 {
+    let mut x = x;
+    while x != NIL && nodes[x as usize].left != NIL {
+        x = nodes[x as usize].left;
+    }
+    x
+}
+
+// Returns the rightmost node of the subtree rooted at x.
+fn last(nodes: &[Node], x: u32) -> u32 {
+    let mut x = x;
+    while x != NIL && nodes[x as usize].right != NIL {
+        x = nodes[x as usize].right;
+    }
+    x
+}
+
+// Returns the next node in key order, or NIL when x is the last one.
+fn successor(nodes: &[Node], x: u32) -> u32 {
+    if nodes[x as usize].right != NIL {
+        return first(nodes, nodes[x as usize].right);
+    }
+    let mut x = x;
+    let mut p = nodes[x as usize].parent;
+    while p != NIL && x == nodes[p as usize].right {
+        x = p;
+        p = nodes[p as usize].parent;
+    }
+    p
+}
+
+// Returns the previous node in key order, or NIL when x is the first one.
+fn predecessor(nodes: &[Node], x: u32) -> u32 {
+    if nodes[x as usize].left != NIL {
+        return last(nodes, nodes[x as usize].left);
+    }
+    let mut x = x;
+    let mut p = nodes[x as usize].parent;
+    while p != NIL && x == nodes[p as usize].left {
+        x = p;
+        p = nodes[p as usize].parent;
+    }
+    p
+}
+
+impl RawIter {
+    fn new(nodes: &[Node], root: u32) -> RawIter {
+        RawIter {
+            front: first(nodes, root),
+            back: last(nodes, root),
+        }
+    }
+
+    fn next_node(&mut self, nodes: &[Node]) -> u32 {
+        let x = self.front;
+        if x == NIL {
+            return x;
+        }
+        if x == self.back {
+            self.front = NIL;
+            self.back = NIL;
+        } else {
+            self.front = successor(nodes, x);
+        }
+        x
+    }
+
+    fn next_back_node(&mut self, nodes: &[Node]) -> u32 {
+        let x = self.back;
+        if x == NIL {
+            return x;
+        }
+        if x == self.front {
+            self.front = NIL;
+            self.back = NIL;
+        } else {
+            self.back = predecessor(nodes, x);
+        }
+        x
+    }
+}
+
+impl<'a, K, V> RawIterMut<'a, K, V> {
+    fn new(map: &'a mut ArenaRBTreeMap<K, V>, raw: RawIter) -> RawIterMut<'a, K, V> {
+        RawIterMut {
+            nodes: &map.nodes,
+            entries: map.entries.iter_mut().map(Option::as_mut).collect(),
+            raw,
+        }
+    }
+
+    // Takes the entry of a node visited by the traversal.
+    fn entry(&mut self, x: u32) -> Option<(&'a K, &'a mut V)> {
+        if x == NIL {
+            return None;
+        }
+        self.entries[x as usize].take().map(|(k, v)| (&*k, v))
+    }
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let x = self.raw.next_node(self.nodes);
+        self.entry(x)
+    }
+
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let x = self.raw.next_back_node(self.nodes);
+        self.entry(x)
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        self.map.entry_of(self.raw.next_node(&self.map.nodes))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        self.map.entry_of(self.raw.next_back_node(&self.map.nodes))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a K> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a V> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a mut V> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for ValuesMut<'a, K, V> {}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        let x = self.raw.next_node(&self.map.nodes);
+        self.map.entries[x as usize].take()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+
+    fn last(mut self) -> Option<(K, V)> {
+        self.next_back()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        let x = self.raw.next_back_node(&self.map.nodes);
+        self.map.entries[x as usize].take()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<K> {
+        self.next_back()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoKeys<K, V> {
+    fn next_back(&mut self) -> Option<K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+impl<K, V> FusedIterator for IntoKeys<K, V> {}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<V> {
+        self.next_back()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoValues<K, V> {
+    fn next_back(&mut self) -> Option<V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+impl<K, V> FusedIterator for IntoValues<K, V> {}
+
+impl<K, V> IntoIterator for ArenaRBTreeMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Gets an owning iterator over the entries of the map, sorted by key.
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            raw: RawIter::new(&self.nodes, self.root),
+            length: self.size,
+            map: self,
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a ArenaRBTreeMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a mut ArenaRBTreeMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.map.entry_of(self.raw.next_node(&self.map.nodes))
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.map.entry_of(self.raw.next_back_node(&self.map.nodes))
+    }
+}
+
+impl<'a, K, V> FusedIterator for Range<'a, K, V> {}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V> FusedIterator for RangeMut<'a, K, V> {}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of the default function.
+    /// The function is given a reference to the key that was moved during the `entry` call.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the VacantEntry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key,
+    /// and returns a mutable reference to it.
+    ///
+    /// The new node is linked under the parent found by [`ArenaRBTreeMap::entry`],
+    /// so no second search is made.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry {
+            map,
+            key,
+            parent,
+            dir,
+        } = self;
+        let x = map.insert_node(parent, dir, key, value);
+        &mut map.entry_mut(x).1
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        self.map.key(self.node)
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        self.map.entry_of(self.node).unwrap().1
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    ///
+    /// If you need a reference to the `OccupiedEntry` that may outlive the
+    /// destruction of the `Entry` value, see [`into_mut`].
+    ///
+    /// [`into_mut`]: OccupiedEntry::into_mut
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entry_mut(self.node).1
+    }
+
+    /// Converts the entry into a mutable reference to its value.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entry_mut(self.node).1
+    }
+
+    /// Sets the value of the entry with the `OccupiedEntry`'s key,
+    /// and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        replace(self.get_mut(), value)
+    }
+
+    /// Takes the value of the entry out of the map, and returns it.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Take ownership of the key and value from the map.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_node(self.node)
+    }
+}
+
+impl<'a, K, V, F> Iterator for ExtractIf<'a, K, V, F>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.next != NIL {
+            let x = self.next;
+            // removal relinks nodes without moving them, so the successor
+            // stays valid whether or not x is removed
+            self.next = self.map.successor(x);
+            let (key, value) = self.map.entry_mut(x);
+            if (self.pred)(key, value) {
+                return Some(self.map.remove_node(x));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len()))
+    }
+}
+
+impl<'a, K, V, F> FusedIterator for ExtractIf<'a, K, V, F> where F: FnMut(&K, &mut V) -> bool {}
+
+impl<'a, K, V> Clone for Cursor<'a, K, V> {
+    fn clone(&self) -> Self {
+        Cursor {
+            current: self.current,
+            map: self.map,
+        }
+    }
+}
+
+impl<'a, K, V> Cursor<'a, K, V> {
+    /// Moves the cursor to the next element of the `ArenaRBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the first element of the `ArenaRBTreeMap`. If it is pointing to the last
+    /// element of the `ArenaRBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_next(&mut self) {
+        self.current = self.map.next_of(self.current);
+    }
+
+    /// Moves the cursor to the previous element of the `ArenaRBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the last element of the `ArenaRBTreeMap`. If it is pointing to the first
+    /// element of the `ArenaRBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_prev(&mut self) {
+        self.current = self.map.prev_of(self.current);
+    }
+
+    /// Returns a reference to the key of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key(&self) -> Option<&'a K> {
+        self.key_value().map(|(k, _)| k)
+    }
+
+    /// Returns a reference to the value of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn value(&self) -> Option<&'a V> {
+        self.key_value().map(|(_, v)| v)
+    }
+
+    /// Returns a reference to the key and value of the element that the cursor
+    /// is currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key_value(&self) -> Option<(&'a K, &'a V)> {
+        self.map.entry_of(self.current)
+    }
+
+    /// Returns a reference to the next element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the first element of the `ArenaRBTreeMap`. If it is pointing to the last
+    /// element of the `ArenaRBTreeMap` then this returns `None`.
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        self.map.entry_of(self.map.next_of(self.current))
+    }
+
+    /// Returns a reference to the previous element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the last element of the `ArenaRBTreeMap`. If it is pointing to the first
+    /// element of the `ArenaRBTreeMap` then this returns `None`.
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        self.map.entry_of(self.map.prev_of(self.current))
+    }
+}
+
+impl<'a, K, V> CursorMut<'a, K, V> {
+    /// Moves the cursor to the next element of the `ArenaRBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the first element of the `ArenaRBTreeMap`. If it is pointing to the last
+    /// element of the `ArenaRBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_next(&mut self) {
+        self.current = self.map.next_of(self.current);
+    }
+
+    /// Moves the cursor to the previous element of the `ArenaRBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the last element of the `ArenaRBTreeMap`. If it is pointing to the first
+    /// element of the `ArenaRBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_prev(&mut self) {
+        self.current = self.map.prev_of(self.current);
+    }
+
+    /// Returns a reference to the key of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key(&self) -> Option<&K> {
+        self.map.entry_of(self.current).map(|(k, _)| k)
+    }
+
+    /// Returns a reference to the value of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn value(&self) -> Option<&V> {
+        self.map.entry_of(self.current).map(|(_, v)| v)
+    }
+
+    /// Returns a reference to the key and value of the element that the cursor
+    /// is currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key_value(&self) -> Option<(&K, &V)> {
+        self.map.entry_of(self.current)
+    }
+
+    /// Returns a mutable reference to the value of the element that the cursor
+    /// is currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        if self.current == NIL {
+            return None;
+        }
+        Some(&mut self.map.entry_mut(self.current).1)
+    }
+
+    /// Returns a reference to the key and a mutable reference to the value of
+    /// the next element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the first element of the `ArenaRBTreeMap`. If it is pointing to the last
+    /// element of the `ArenaRBTreeMap` then this returns `None`.
+    pub fn peek_next(&mut self) -> Option<(&K, &mut V)> {
+        let next = self.map.next_of(self.current);
+        if next == NIL {
+            return None;
+        }
+        let (key, value) = self.map.entry_mut(next);
+        Some((&*key, value))
+    }
+
+    /// Returns a reference to the key and a mutable reference to the value of
+    /// the previous element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the last element of the `ArenaRBTreeMap`. If it is pointing to the first
+    /// element of the `ArenaRBTreeMap` then this returns `None`.
+    pub fn peek_prev(&mut self) -> Option<(&K, &mut V)> {
+        let prev = self.map.prev_of(self.current);
+        if prev == NIL {
+            return None;
+        }
+        let (key, value) = self.map.entry_mut(prev);
+        Some((&*key, value))
+    }
+
+    /// Returns a read-only cursor pointing to the current element.
+    ///
+    /// The lifetime of the returned `Cursor` is bound to that of the
+    /// `CursorMut`, which means it cannot outlive the `CursorMut` and that the
+    /// `CursorMut` is frozen for the lifetime of the `Cursor`.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V> {
+        Cursor {
+            current: self.current,
+            map: self.map,
+        }
+    }
+
+    /// Inserts a new element into the `ArenaRBTreeMap` after the current one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new element is
+    /// inserted at the front of the `ArenaRBTreeMap`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if:
+    /// - the given key compares less than or equal to the current element (if
+    ///   any).
+    /// - the given key compares greater than or equal to the next element (if
+    ///   any).
+    pub fn insert_after(&mut self, key: K, value: V)
+    where
+        K: Ord,
+    {
+        let next = self.map.next_of(self.current);
+        if self.current != NIL {
+            assert!(
+                key > *self.map.key(self.current),
+                "key must be ordered above the current element"
+            );
+        }
+        if next != NIL {
+            assert!(
+                key < *self.map.key(next),
+                "key must be ordered below the next element"
+            );
+        }
+
+        // the new node becomes the left child of the next node, when that
+        // slot is free, or the right child of the current node otherwise
+        if next != NIL && self.map.node(next).left == NIL {
+            self.map.insert_node(next, Direction::Left, key, value);
+        } else {
+            self.map
+                .insert_node(self.current, Direction::Right, key, value);
+        }
+    }
+
+    /// Inserts a new element into the `ArenaRBTreeMap` before the current one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new element is
+    /// inserted at the end of the `ArenaRBTreeMap`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if:
+    /// - the given key compares greater than or equal to the current element
+    ///   (if any).
+    /// - the given key compares less than or equal to the previous element (if
+    ///   any).
+    pub fn insert_before(&mut self, key: K, value: V)
+    where
+        K: Ord,
+    {
+        let prev = self.map.prev_of(self.current);
+        if self.current != NIL {
+            assert!(
+                key < *self.map.key(self.current),
+                "key must be ordered below the current element"
+            );
+        }
+        if prev != NIL {
+            assert!(
+                key > *self.map.key(prev),
+                "key must be ordered above the previous element"
+            );
+        }
+
+        // symmetric case: right child of the previous node, or left child
+        // of the current node
+        if prev != NIL && self.map.node(prev).right == NIL {
+            self.map.insert_node(prev, Direction::Right, key, value);
+        } else {
+            self.map
+                .insert_node(self.current, Direction::Left, key, value);
+        }
+    }
+
+    /// Removes the current element from the `ArenaRBTreeMap`.
+    ///
+    /// The element that was removed is returned, and the cursor is
+    /// moved to point to the next element in the `ArenaRBTreeMap`.
+    ///
+    /// If the cursor is currently pointing to the "ghost" non-element then no element
+    /// is removed and `None` is returned. The cursor is not moved in this case.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.current == NIL {
+            return None;
+        }
+        let current = self.current;
+        self.current = self.map.successor(current);
+        Some(self.map.remove_node(current))
+    }
+
+    /// Removes the current element from the `ArenaRBTreeMap`.
+    ///
+    /// The element that was removed is returned, and the cursor is
+    /// moved to point to the previous element in the `ArenaRBTreeMap`.
+    ///
+    /// If the cursor is currently pointing to the "ghost" non-element then no element
+    /// is removed and `None` is returned. The cursor is not moved in this case.
+    pub fn remove_current_and_move_back(&mut self) -> Option<(K, V)> {
+        if self.current == NIL {
+            return None;
+        }
+        let current = self.current;
+        self.current = self.map.predecessor(current);
+        Some(self.map.remove_node(current))
+    }
+}
+
+impl<K, V> Default for ArenaRBTreeMap<K, V> {
+    /// Creates an empty `ArenaRBTreeMap`.
+    fn default() -> ArenaRBTreeMap<K, V> {
+        ArenaRBTreeMap::new()
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for ArenaRBTreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for ArenaRBTreeMap<K, V> {
+    fn eq(&self, other: &ArenaRBTreeMap<K, V>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for ArenaRBTreeMap<K, V> {}
+
+impl<K: PartialOrd, V: PartialOrd> PartialOrd for ArenaRBTreeMap<K, V> {
+    fn partial_cmp(&self, other: &ArenaRBTreeMap<K, V>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord> Ord for ArenaRBTreeMap<K, V> {
+    fn cmp(&self, other: &ArenaRBTreeMap<K, V>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash> Hash for ArenaRBTreeMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, Q, V> Index<&Q> for ArenaRBTreeMap<K, V>
+where
+    K: Borrow<Q>,
+    Q: ?Sized + Ord,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `ArenaRBTreeMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for ArenaRBTreeMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> ArenaRBTreeMap<K, V> {
+        let mut map = ArenaRBTreeMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for ArenaRBTreeMap<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K: Ord + Copy, V: Copy> Extend<(&'a K, &'a V)> for ArenaRBTreeMap<K, V> {
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArenaRBTreeMap, NIL};
+    use std::ops::Bound;
+
+    fn is_valid<K, V>(map: &ArenaRBTreeMap<K, V>) -> bool {
+        !map.is_red(map.root)
+            && map.parent_of(map.root) == NIL
+            && map.is_234tree(map.root) == Some(map.black_height(map.root))
+            && map.check_links(map.root) == Some(map.len())
+            && map.count_free() == Some(map.nodes.len() - map.len())
+    }
+
+    #[quickcheck]
+    fn insert_and_remove(v: Vec<i32>) -> bool {
+        let mut map = ArenaRBTreeMap::new();
+
+        for &x in v.iter() {
+            map.insert(x, x);
+            if !is_valid(&map) {
+                return false;
+            }
+        }
+        for &x in v.iter() {
+            map.remove(&x);
+            if !is_valid(&map) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    #[quickcheck]
+    fn slots_are_reused(v: Vec<i32>) -> bool {
+        let mut map = ArenaRBTreeMap::new();
+        for &x in v.iter() {
+            map.insert(x, x);
+        }
+        let slots = map.nodes.len();
+        for &x in v.iter().step_by(2) {
+            map.remove(&x);
+        }
+        for &x in v.iter().step_by(2) {
+            map.insert(x, x);
+        }
+        is_valid(&map) && map.nodes.len() == slots
+    }
+
+    #[quickcheck]
+    fn shrink_to_fit(v: Vec<i32>, w: Vec<i32>) -> bool {
+        let mut map = ArenaRBTreeMap::new();
+        for &x in v.iter() {
+            map.insert(x, x);
+        }
+        for &x in w.iter() {
+            map.remove(&x);
+        }
+        let entries: Vec<(i32, i32)> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        map.shrink_to_fit();
+
+        // the nodes now sit in key order at the front of the arena
+        let mut raw = super::RawIter::new(&map.nodes, map.root);
+        let in_order = (0..map.len() as u32).all(|x| raw.next_node(&map.nodes) == x);
+        is_valid(&map)
+            && in_order
+            && map.nodes.len() == map.len()
+            && map.iter().map(|(&k, &v)| (k, v)).eq(entries)
+    }
+
+    #[quickcheck]
+    fn split_off_and_append(v: Vec<i32>, w: Vec<i32>, at: i32) -> bool {
+        let mut map = ArenaRBTreeMap::new();
+        for &x in v.iter() {
+            map.insert(x, x);
+        }
+        let mut right = map.split_off(&at);
+        if !is_valid(&map) || !is_valid(&right) {
+            return false;
+        }
+
+        // disjoint key ranges are joined, overlapping ones are merged
+        let mut other = ArenaRBTreeMap::new();
+        for &x in w.iter() {
+            other.insert(x, x);
+        }
+        map.append(&mut right);
+        map.append(&mut other);
+        is_valid(&map) && right.is_empty() && other.is_empty()
+    }
+
+    #[quickcheck]
+    fn join(left: Vec<u16>, right: Vec<u16>) -> bool {
+        let mut l = ArenaRBTreeMap::new();
+        let mut r = ArenaRBTreeMap::new();
+        for &x in left.iter() {
+            l.insert(x as i32, ());
+        }
+        for &x in right.iter() {
+            r.insert(x as i32 + 100_000, ());
+        }
+        let map = ArenaRBTreeMap::join(l, 99_999, (), r);
+        is_valid(&map)
+    }
+
+    #[quickcheck]
+    fn cursor_edits(ops: Vec<(u8, bool)>) -> bool {
+        let mut map = ArenaRBTreeMap::new();
+        for x in (0..200).step_by(2) {
+            map.insert(x, ());
+        }
+        let mut cursor = map.lower_bound_mut(Bound::Unbounded);
+        for &(op, forward) in ops.iter() {
+            match op % 4 {
+                0 if forward => cursor.move_next(),
+                0 => cursor.move_prev(),
+                1 => {
+                    cursor.remove_current();
+                }
+                _ => {
+                    // insert the odd key next to an even one, if it's missing
+                    if let Some(&k) = cursor.key() {
+                        let free = if forward {
+                            cursor.peek_next().is_none_or(|(&n, _)| n > k + 1)
+                        } else {
+                            cursor.peek_prev().is_none_or(|(&p, _)| p < k - 1)
+                        };
+                        if free && forward {
+                            cursor.insert_after(k + 1, ());
+                        } else if free {
+                            cursor.insert_before(k - 1, ());
+                        }
+                    }
+                }
+            }
+        }
+        is_valid(&map)
+    }
+
+    #[test]
+    fn from_sorted_sizes() {
+        for n in 0..300 {
+            let map = ArenaRBTreeMap::from_sorted_iter((0..n).map(|x| (x, x)));
+            assert!(is_valid(&map), "invalid tree of {} entries", n);
+            assert!(map.keys().copied().eq(0..n));
+        }
+    }
+}
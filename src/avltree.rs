@@ -1,219 +1,421 @@
-use std::cell::RefCell;
-use std::cmp::{max, Ordering};
-use std::rc::Rc;
+use std::borrow::Borrow;
 use std::fmt::Display;
 
-pub struct Node<T> {
-    key: T,
-    left: Link<T>,
-    right: Link<T>,
-    height: i32,
-}
-
-pub type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+use crate::avltree_map::AVLTreeMap;
+use crate::comparator::{Comparator, OrdComparator};
 
-pub struct AVLTree<T> {
-    root: Link<T>,
+// The keys are stored in an `AVLTreeMap` with `()` values, which does the
+// balancing for both.
+pub struct AVLTree<T, C = OrdComparator> {
+    map: AVLTreeMap<T, (), C>,
 }
 
-impl<T> Node<T> {
-    fn new(key: T) -> Link<T> {
-        Some(Rc::new(RefCell::new(Node {
-            key: key,
-            left: None,
-            right: None,
-            height: 1,
-        })))
+impl<T: Ord> AVLTree<T> {
+    pub fn new() -> Self {
+        Self::with_comparator(OrdComparator)
     }
 }
 
-impl<T: Ord> AVLTree<T> {
-    pub fn new() -> Self {
+impl<T, C: Comparator<T>> AVLTree<T, C> {
+    /// Makes a new, empty `AVLTree` ordering its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self {
         AVLTree {
-            root: None
+            map: AVLTreeMap::with_comparator(cmp),
         }
     }
 
-    fn add_inner(mut root: Link<T>, key: T) -> Link<T> {
-        match root.take() {
-            Some(node) => {
-                {
-                    let mut x = node.borrow_mut();
-                    match key.cmp(&x.key) {
-                        Ordering::Less => {
-                            x.left = Self::add_inner(x.left.take(), key)
-                        },
-                        Ordering::Greater => x.right = Self::add_inner(x.right.take(), key),
-                        _ => return None
-                    }
-                    x.height = std::cmp::max(Self::tree_height(&x.left), Self::tree_height(&x.right)) + 1;
-                }
-                Self::balance(Some(node))
-            }
-            None => {
-                Node::new(key)
-            }
-        }
+    #[cfg(test)]
+    fn is_balanced(&self) -> bool {
+        self.map.is_valid()
     }
 
-    fn rotate_left(mut root: Link<T>) -> Link<T> {
-        root.take().map(|x| {
-            let rch = x.borrow_mut().right.take().unwrap();
-            let rchlch = rch.borrow_mut().left.take();
-            {
-                let mut x = x.borrow_mut();
-                x.right = rchlch;
-                x.height = max(Self::tree_height(&x.left), Self::tree_height(&x.right)) + 1;
-            }
-            {
-                let mut y = rch.borrow_mut();
-                y.left = Some(x);
-                y.height = max(Self::tree_height(&y.left), Self::tree_height(&y.right) + 1);
-            }
-            rch
-        })
+    /// Adds a key to the tree.
+    ///
+    /// Returns whether the key was newly inserted. If the tree already contains
+    /// an equal key, the tree is not modified and the stored key is kept.
+    pub fn add(&mut self, key: T) -> bool {
+        self.map.insert(key, ()).is_none()
     }
 
-    fn rotate_right(mut root: Link<T>) -> Link<T> {
-        root.take().map(|x| {
-            let lch = {
-                x.borrow_mut().left.take().unwrap()
-            };
-            let lchrch = {
-                lch.borrow_mut().right.take()
-            };
-            {
-                let mut x = x.borrow_mut();
-                x.left = lchrch;
-                x.height = max(Self::tree_height(&x.left), Self::tree_height(&x.right)) + 1;
-            }
-            {
-                let mut y = lch.borrow_mut();
-                y.right = Some(x);
-                y.height = max(Self::tree_height(&y.left), Self::tree_height(&y.right) + 1);
-            }
-            lch
-        })
+    /// Adds a key to the tree, replacing the equal key already in the tree,
+    /// if any, and returning it.
+    pub fn replace(&mut self, key: T) -> Option<T> {
+        self.map.insert_entry(key, (), true).map(|(k, _)| k)
+    }
+
+    /// Removes a key from the tree, returning the stored key if it was present.
+    ///
+    /// The key may be any borrowed form of the tree's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.remove_entry(key).map(|(k, _)| k)
+    }
+
+    /// Removes the smallest key from the tree and returns it, if any.
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.map.pop_first().map(|(k, _)| k)
+    }
+
+    /// Removes the largest key from the tree and returns it, if any.
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.map.pop_last().map(|(k, _)| k)
+    }
+
+    /// Returns `true` if the tree contains a key equal to the given one.
+    ///
+    /// The key may be any borrowed form of the tree's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.contains_key(key)
     }
 
-    fn tree_height(node: &Link<T>) -> i32 {
-        node.as_ref().map_or(0, |x| x.borrow().height)
+    /// Returns a reference to the key in the tree equal to the given one, if any.
+    ///
+    /// The key may be any borrowed form of the tree's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.get_key_value(key).map(|(k, _)| k)
     }
 
-    fn balance_factor(node: &Link<T>) -> i32 {
-        node.as_ref().map_or(0, |x| {
-            let x = x.borrow();
-            Self::tree_height(&x.left) - Self::tree_height(&x.right)
-        })
+    /// Returns a mutable reference to the key in the tree equal to the given
+    /// one, if any.
+    ///
+    /// The key may be any borrowed form of the tree's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type. The key
+    /// must not be changed in a way that changes its order relative to the
+    /// other keys; that leaves the tree in an unspecified state.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.get_key_mut(key)
     }
+}
 
-    fn balance(root: Link<T>) -> Link<T> {
-        let bf = Self::balance_factor(&root);
-        if bf >= -1 && bf <= 1 {
-            return root;
+impl<T, C: Default> Default for AVLTree<T, C> {
+    fn default() -> Self {
+        AVLTree {
+            map: AVLTreeMap::default(),
         }
-        if let Some(x) = root {
-            match bf {
-                -2 => {
-                    let sub_bf = Self::balance_factor(&x.borrow().right);
-                    if sub_bf > 0 {
-                        let mut x = x.borrow_mut();
-                        x.right = Self::rotate_right(x.right.take());
-                    }
-                    Self::rotate_left(Some(x))
-                }
-                2 => {
-                    let sub_bf = Self::balance_factor(&x.borrow().left);
-                    if sub_bf < 0 {
-                        let mut x = x.borrow_mut();
-                        x.left = Self::rotate_left(x.left.take());
-                    }
-                    Self::rotate_right(Some(x))
-                }
-                _ => unreachable!(),
+    }
+}
+
+impl<T: Display, C> AVLTree<T, C> {
+    pub fn traverse(&self) {
+        for key in self.map.keys() {
+            println!("=>{}", key);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::AVLTree;
+    use crate::avltree_map::AVLTreeMap;
+    use crate::comparator::Comparator;
+    use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeSeq, Serializer};
+    use std::cmp::Ordering;
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    impl<T: Serialize, C> Serialize for AVLTree<T, C> {
+        /// Serializes the tree as a sequence of its keys in ascending order.
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(self.map.len()))?;
+            for key in self.map.keys() {
+                seq.serialize_element(key)?;
             }
-        } else {
-            None
+            seq.end()
         }
     }
 
-    pub fn add(&mut self, key: T) {
-        self.root = Self::add_inner(self.root.take(), key)
+    impl<'de, T, C> Deserialize<'de> for AVLTree<T, C>
+    where
+        T: Deserialize<'de>,
+        C: Comparator<T> + Default,
+    {
+        /// Deserializes a sequence of keys, which is built in O(n) time when it
+        /// is sorted. Fails if a key appears twice.
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(SeqVisitor(PhantomData))
+        }
     }
 
-    fn find(root: &Link<T>, key: T) -> Link<T> {
-        root.as_ref().and_then(|node| {
-            let x = node.borrow();
-            if key.lt(&x.key) {
-                Self::find(&x.left, key)
-            } else if x.key.lt(&key) {
-                Self::find(&x.right, key)
-            } else {
-                Some(node.clone())
+    struct SeqVisitor<T, C>(PhantomData<AVLTree<T, C>>);
+
+    impl<'de, T, C> Visitor<'de> for SeqVisitor<T, C>
+    where
+        T: Deserialize<'de>,
+        C: Comparator<T> + Default,
+    {
+        type Value = AVLTree<T, C>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a sequence of unique keys")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let cmp = C::default();
+            // the size hint comes from the input, so it is not trusted with
+            // a large allocation
+            let mut keys: Vec<T> = Vec::with_capacity(access.size_hint().unwrap_or(0).min(4096));
+            let mut sorted = true;
+            while let Some(key) = access.next_element()? {
+                if let Some(last) = keys.last() {
+                    match cmp.compare(last, &key) {
+                        Ordering::Less => {}
+                        Ordering::Equal => return Err(duplicate_key(keys.len())),
+                        Ordering::Greater => sorted = false,
+                    }
+                }
+                keys.push(key);
             }
-        })
-    }
-
-    pub fn contains(&self, key: T) -> bool {
-        Self::find(&self.root, key).is_some()
-    }
-
-    fn is_balanced(root: &Link<T>) -> bool {
-        match root {
-            Some(x) => {
-                let x = x.borrow();
-                let lh = Self::tree_height(&x.left);
-                let rh = Self::tree_height(&x.right);
-                let lch_ok = Self::is_balanced(&x.left);
-                let rch_ok = Self::is_balanced(&x.right);
-                // println!("{} {} {} {} {}", lch_ok, rch_ok, x.height, lh, rh);
-                lch_ok && rch_ok
-                    && x.height == max(lh, rh) + 1
-                    && lh - rh <= 1
-                    && lh - rh >= -1
+
+            if !sorted {
+                // sorting keeps equal keys in input order, so the second
+                // of two equal neighbours is the later one in the input
+                let mut indexed: Vec<(usize, T)> = keys.into_iter().enumerate().collect();
+                indexed.sort_by(|a, b| cmp.compare(&a.1, &b.1));
+                if let Some(w) = indexed.windows(2).find(|w| cmp.compare(&w[0].1, &w[1].1).is_eq()) {
+                    return Err(duplicate_key(w[1].0));
+                }
+                keys = indexed.into_iter().map(|(_, key)| key).collect();
             }
-            None => true
+            let entries = keys.into_iter().map(|key| (key, ())).collect();
+            Ok(AVLTree {
+                map: AVLTreeMap::from_sorted_unique(cmp, entries),
+            })
         }
     }
-}
 
-impl<T: Display> AVLTree<T> {
-    pub fn traverse_inner(node: &Link<T>) {
-        node.as_ref().map(|x| {
-            Self::traverse_inner(&x.borrow().left);
-            println!("=>{}", x.borrow().key);
-            Self::traverse_inner(&x.borrow().right);
-        });
-    }
-    pub fn traverse(&self) {
-        Self::traverse_inner(&self.root);
+    fn duplicate_key<E: Error>(index: usize) -> E {
+        E::custom(format_args!("duplicate key at element {} of the sequence", index))
     }
 }
 
-
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test {
     use crate::avltree::AVLTree;
+    use std::collections::BTreeSet;
+
+    fn same_keys(t: &AVLTree<i32>, expected: &BTreeSet<i32>) -> bool {
+        t.map.keys().eq(expected.iter())
+    }
+
+    fn build(v: &[i32]) -> (AVLTree<i32>, BTreeSet<i32>) {
+        let mut t = AVLTree::new();
+        let mut expected = BTreeSet::new();
+        for &x in v {
+            assert_eq!(t.add(x), expected.insert(x));
+        }
+        (t, expected)
+    }
 
     #[test]
     fn basics() {
         let mut t = AVLTree::new();
         t.add(1);
-        assert_eq!(AVLTree::is_balanced(&t.root), true);
+        assert_eq!(t.is_balanced(), true);
         t.add(5);
-        assert_eq!(AVLTree::is_balanced(&t.root), true);
+        assert_eq!(t.is_balanced(), true);
         t.add(3);
-        assert_eq!(AVLTree::is_balanced(&t.root), true);
+        assert_eq!(t.is_balanced(), true);
         t.add(2);
-        assert_eq!(AVLTree::is_balanced(&t.root), true);
+        assert_eq!(t.is_balanced(), true);
         t.add(4);
-        assert_eq!(AVLTree::is_balanced(&t.root), true);
-        assert_eq!(t.contains(1), true);
-        assert_eq!(t.contains(2), true);
-        assert_eq!(t.contains(3), true);
-        assert_eq!(t.contains(4), true);
-        assert_eq!(t.contains(5), true);
-        assert_eq!(t.contains(6), false);
-        assert_eq!(t.contains(0), false);
-    }
-}
\ No newline at end of file
+        assert_eq!(t.is_balanced(), true);
+        assert_eq!(t.contains(&1), true);
+        assert_eq!(t.contains(&2), true);
+        assert_eq!(t.contains(&3), true);
+        assert_eq!(t.contains(&4), true);
+        assert_eq!(t.contains(&5), true);
+        assert_eq!(t.contains(&6), false);
+        assert_eq!(t.contains(&0), false);
+    }
+
+    #[test]
+    fn comparator() {
+        let mut t = AVLTree::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        for x in 0..20 {
+            t.add(x);
+            assert!(t.is_balanced());
+        }
+        assert!((0..20).all(|x| t.contains(&x)));
+        assert!(!t.contains(&20));
+
+        let mut t = AVLTree::with_comparator(|a: &f64, b: &f64| a.total_cmp(b));
+        t.add(f64::NAN);
+        t.add(-0.0);
+        t.add(0.0);
+        assert!(t.contains(&f64::NAN));
+        assert!(t.contains(&0.0));
+        assert!(!t.contains(&1.0));
+    }
+
+    #[test]
+    fn get() {
+        // compares only the first field
+        let mut t = AVLTree::with_comparator(|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        for x in 0..10 {
+            t.add((x, 'a'));
+        }
+        assert_eq!(t.get(&(3, ' ')), Some(&(3, 'a')));
+        assert_eq!(t.get(&(10, ' ')), None);
+        t.get_mut(&(3, ' ')).unwrap().1 = 'b';
+        assert_eq!(t.get(&(3, ' ')), Some(&(3, 'b')));
+        assert_eq!(t.get_mut(&(-1, ' ')), None);
+        assert_eq!(t.remove(&(3, ' ')), Some((3, 'b')));
+    }
+
+    #[test]
+    fn borrowed_keys() {
+        let mut t = AVLTree::new();
+        for name in ["carol", "alice", "bob"] {
+            t.add(name.to_string());
+        }
+        assert!(t.contains("bob"));
+        assert!(!t.contains("dave"));
+        assert_eq!(t.get("alice").map(String::as_str), Some("alice"));
+        t.get_mut("carol").unwrap().push('!');
+        assert_eq!(t.remove("carol!").as_deref(), Some("carol!"));
+        assert_eq!(t.remove("carol"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn build_sorted() {
+        for n in 0..100 {
+            let t = AVLTree {
+                map: crate::avltree_map::AVLTreeMap::from_sorted_unique(
+                    crate::comparator::OrdComparator,
+                    (0..n).map(|x| (x, ())).collect(),
+                ),
+            };
+            assert!(t.is_balanced());
+            assert!((0..n).all(|x| t.contains(&x)));
+            assert!(!t.contains(&n));
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let mut t = AVLTree::new();
+        for x in 0..10 {
+            t.add(x);
+        }
+        assert_eq!(t.remove(&3), Some(3));
+        assert_eq!(t.remove(&3), None);
+        assert!(!t.contains(&3));
+        assert!(t.is_balanced());
+        assert_eq!(t.pop_min(), Some(0));
+        assert_eq!(t.pop_max(), Some(9));
+        for x in [1, 2, 4, 5, 6, 7, 8] {
+            assert!(t.contains(&x));
+        }
+        while t.pop_min().is_some() {
+            assert!(t.is_balanced());
+        }
+        assert_eq!(t.pop_max(), None);
+        assert_eq!(t.remove(&1), None);
+    }
+
+    #[quickcheck]
+    fn remove_like_btreeset(v: Vec<i32>, w: Vec<i32>) -> bool {
+        let (mut t, mut expected) = build(&v);
+        for x in w.iter().chain(v.iter()) {
+            if t.remove(x) != expected.take(x) {
+                return false;
+            }
+            if !t.is_balanced() || !same_keys(&t, &expected) {
+                return false;
+            }
+        }
+        t.map.is_empty()
+    }
+
+    #[quickcheck]
+    fn pop_like_btreeset(v: Vec<i16>, from_max: Vec<bool>) -> bool {
+        let v: Vec<i32> = v.into_iter().map(i32::from).collect();
+        let (mut t, mut expected) = build(&v);
+        // then empty the tree from its max
+        for max in from_max.into_iter().chain(v.iter().map(|_| true)) {
+            let popped = if max {
+                (t.pop_max(), expected.pop_last())
+            } else {
+                (t.pop_min(), expected.pop_first())
+            };
+            if popped.0 != popped.1 {
+                return false;
+            }
+            if !t.is_balanced() || !same_keys(&t, &expected) {
+                return false;
+            }
+        }
+        t.map.is_empty()
+    }
+
+    #[quickcheck]
+    fn add_like_btreeset(v: Vec<i8>) -> bool {
+        let mut t = AVLTree::new();
+        let mut expected = BTreeSet::new();
+        for &x in v.iter() {
+            let x = i32::from(x);
+            if t.add(x) != expected.insert(x) {
+                return false;
+            }
+            if !t.is_balanced() || !same_keys(&t, &expected) {
+                return false;
+            }
+        }
+        v.iter().all(|&x| t.contains(&i32::from(x)))
+    }
+
+    #[quickcheck]
+    fn replace_like_btreeset(v: Vec<i8>) -> bool {
+        let mut t = AVLTree::new();
+        let mut expected = BTreeSet::new();
+        for &x in v.iter() {
+            let x = i32::from(x);
+            if t.replace(x) != expected.replace(x) {
+                return false;
+            }
+            if !t.is_balanced() || !same_keys(&t, &expected) {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn replace_swaps_equal_keys() {
+        // compares only the first field
+        let mut t = AVLTree::with_comparator(|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        assert!(t.add((1, 'a')));
+        assert!(t.add((2, 'b')));
+        assert!(!t.add((1, 'c')));
+        assert_eq!(t.replace((1, 'd')), Some((1, 'a')));
+        assert_eq!(t.replace((3, 'e')), None);
+        assert_eq!(t.remove(&(1, ' ')), Some((1, 'd')));
+        assert_eq!(t.pop_min(), Some((2, 'b')));
+        assert_eq!(t.pop_min(), Some((3, 'e')));
+    }
+}
@@ -0,0 +1,777 @@
+use std::borrow::Borrow;
+use std::cmp::{max, Ordering};
+use std::fmt::{self, Debug};
+use std::iter::FusedIterator;
+use std::mem::replace;
+use std::ops::Index;
+
+use crate::comparator::{Comparator, OrdComparator};
+
+#[derive(Clone)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+    height: i32,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+/// An ordered map based on an AVL tree, with the interface of [`RBTreeMap`].
+///
+/// The heights of the two subtrees of every node differ by at most one, which keeps
+/// the tree shallower than a red-black tree at the cost of more rotations on
+/// updates. Keys are ordered by the comparator `C`, which defaults to their `Ord`
+/// implementation.
+///
+/// [`RBTreeMap`]: crate::rbtree::RBTreeMap
+#[derive(Clone)]
+pub struct AVLTreeMap<K, V, C = OrdComparator> {
+    root: Link<K, V>,
+    size: usize,
+    cmp: C,
+}
+
+/// An iterator over the entries of a `AVLTreeMap`, sorted by key.
+///
+/// This `struct` is created by the [`iter`] method on [`AVLTreeMap`].
+///
+/// [`iter`]: AVLTreeMap::iter
+pub struct Iter<'a, K: 'a, V: 'a> {
+    // nodes whose left subtree has been visited, the next one on top
+    front: Vec<&'a Node<K, V>>,
+    // nodes whose right subtree has been visited, the next one on top
+    back: Vec<&'a Node<K, V>>,
+    length: usize,
+}
+
+/// An iterator over the keys of a `AVLTreeMap`, sorted.
+///
+/// This `struct` is created by the [`keys`] method on [`AVLTreeMap`].
+///
+/// [`keys`]: AVLTreeMap::keys
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+/// An iterator over the values of a `AVLTreeMap`, in order of their keys.
+///
+/// This `struct` is created by the [`values`] method on [`AVLTreeMap`].
+///
+/// [`values`]: AVLTreeMap::values
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+/// An owning iterator over the entries of a `AVLTreeMap`, sorted by key.
+///
+/// This `struct` is created by the `into_iter` method on [`AVLTreeMap`].
+pub struct IntoIter<K, V> {
+    // nodes whose left subtree has been visited, the next one on top
+    stack: Vec<Box<Node<K, V>>>,
+    length: usize,
+}
+
+impl<K, V> Node<K, V> {
+    fn new(key: K, value: V) -> Box<Node<K, V>> {
+        Box::new(Node {
+            key,
+            value,
+            left: None,
+            right: None,
+            height: 1,
+        })
+    }
+
+    fn height(node: &Link<K, V>) -> i32 {
+        node.as_ref().map_or(0, |x| x.height)
+    }
+
+    fn update_height(&mut self) {
+        self.height = max(Self::height(&self.left), Self::height(&self.right)) + 1;
+    }
+
+    fn balance_factor(&self) -> i32 {
+        Self::height(&self.left) - Self::height(&self.right)
+    }
+
+    fn rotate_left(mut x: Box<Node<K, V>>) -> Box<Node<K, V>> {
+        let mut y = x.right.take().unwrap();
+        x.right = y.left.take();
+        x.update_height();
+        y.left = Some(x);
+        y.update_height();
+        y
+    }
+
+    fn rotate_right(mut x: Box<Node<K, V>>) -> Box<Node<K, V>> {
+        let mut y = x.left.take().unwrap();
+        x.left = y.right.take();
+        x.update_height();
+        y.right = Some(x);
+        y.update_height();
+        y
+    }
+
+    // Updates the height of a node whose subtrees are balanced and differ in
+    // height by at most 2, and rotates it back into balance.
+    fn balance(mut x: Box<Node<K, V>>) -> Box<Node<K, V>> {
+        x.update_height();
+        match x.balance_factor() {
+            -2 => {
+                if x.right.as_ref().unwrap().balance_factor() > 0 {
+                    x.right = Some(Self::rotate_right(x.right.take().unwrap()));
+                }
+                Self::rotate_left(x)
+            }
+            2 => {
+                if x.left.as_ref().unwrap().balance_factor() < 0 {
+                    x.left = Some(Self::rotate_left(x.left.take().unwrap()));
+                }
+                Self::rotate_right(x)
+            }
+            _ => x,
+        }
+    }
+
+    fn rebalance(link: &mut Link<K, V>) {
+        if let Some(x) = link.take() {
+            *link = Some(Self::balance(x));
+        }
+    }
+
+    // Detaches the leftmost node of the tree rooted at x,
+    // returns the remaining tree and that node.
+    fn remove_min(mut x: Box<Node<K, V>>) -> (Link<K, V>, Box<Node<K, V>>) {
+        match x.left.take() {
+            None => (x.right.take(), x),
+            Some(left) => {
+                let (left, min) = Self::remove_min(left);
+                x.left = left;
+                (Some(Self::balance(x)), min)
+            }
+        }
+    }
+
+    // Detaches the rightmost node of the tree rooted at x,
+    // returns the remaining tree and that node.
+    fn remove_max(mut x: Box<Node<K, V>>) -> (Link<K, V>, Box<Node<K, V>>) {
+        match x.right.take() {
+            None => (x.left.take(), x),
+            Some(right) => {
+                let (right, last) = Self::remove_max(right);
+                x.right = right;
+                (Some(Self::balance(x)), last)
+            }
+        }
+    }
+
+    // Builds a perfectly balanced tree from the next n entries of iter, which
+    // must be sorted by key and have distinct keys.
+    #[cfg(feature = "serde")]
+    fn build_sorted<I: Iterator<Item = (K, V)>>(n: usize, iter: &mut I) -> Link<K, V> {
+        if n == 0 {
+            return None;
+        }
+        let n_left = (n - 1) / 2;
+        let left = Self::build_sorted(n_left, iter);
+        let (key, value) = iter.next().unwrap();
+        let right = Self::build_sorted(n - 1 - n_left, iter);
+        // the right side has at least as many entries as the left, so it is at least as tall
+        let height = Self::height(&right) + 1;
+        Some(Box::new(Node {
+            key,
+            value,
+            left,
+            right,
+            height,
+        }))
+    }
+}
+
+impl<K, V> AVLTreeMap<K, V> {
+    /// Makes a new, empty `AVLTreeMap`, ordering its keys by their `Ord` implementation.
+    pub fn new() -> AVLTreeMap<K, V> {
+        Self::with_comparator(OrdComparator)
+    }
+}
+
+impl<K, V, C> AVLTreeMap<K, V, C> {
+    /// Makes a new, empty `AVLTreeMap` ordering its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> AVLTreeMap<K, V, C> {
+        AVLTreeMap {
+            root: None,
+            size: 0,
+            cmp,
+        }
+    }
+
+    // Builds a map from entries sorted by `cmp` with distinct keys, in O(n) time.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_sorted_unique(cmp: C, entries: Vec<(K, V)>) -> AVLTreeMap<K, V, C> {
+        let size = entries.len();
+        AVLTreeMap {
+            root: Node::build_sorted(size, &mut entries.into_iter()),
+            size,
+            cmp,
+        }
+    }
+
+    /// Returns the number of elements in the map.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if the map contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Clears the map, removing all elements from the map
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.size = 0;
+    }
+
+    /// Returns the value corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search_node(key).map(|x| &x.value)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search_node_mut(key).map(|x| &mut x.value)
+    }
+
+    // Returns a mutable reference to the stored key equal to `key`, for
+    // `AVLTree::get_mut`. The caller must not change the order of the key.
+    pub(crate) fn get_key_mut<Q>(&mut self, key: &Q) -> Option<&mut K>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search_node_mut(key).map(|x| &mut x.key)
+    }
+
+    /// Returns the key-value pair corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search_node(key).map(|x| (&x.key, &x.value))
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.search_node(key).is_some()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, `None` is returned.
+    ///
+    /// If the map did have this key present, the value is updated, and the old
+    /// value is returned. The key is not updated.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V>
+    where
+        C: Comparator<K>,
+    {
+        self.insert_entry(key, value, false).map(|(_, v)| v)
+    }
+
+    // Inserts a key-value pair, or when an equal key is already in the map,
+    // swaps in the value, and the key too if `replace_key` is set, for
+    // `AVLTree::replace`. Returns the key and value left out of the map.
+    pub(crate) fn insert_entry(&mut self, key: K, value: V, replace_key: bool) -> Option<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        let old = Self::insert_inner(&self.cmp, &mut self.root, key, value, replace_key);
+        if old.is_none() {
+            self.size += 1;
+        }
+        old
+    }
+
+    fn insert_inner(
+        cmp: &C,
+        link: &mut Link<K, V>,
+        key: K,
+        value: V,
+        replace_key: bool,
+    ) -> Option<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        let x = match link {
+            Some(x) => x,
+            None => {
+                *link = Some(Node::new(key, value));
+                return None;
+            }
+        };
+        let old = match cmp.compare(&key, &x.key) {
+            Ordering::Less => Self::insert_inner(cmp, &mut x.left, key, value, replace_key),
+            Ordering::Greater => Self::insert_inner(cmp, &mut x.right, key, value, replace_key),
+            Ordering::Equal if replace_key => {
+                return Some((replace(&mut x.key, key), replace(&mut x.value, value)))
+            }
+            Ordering::Equal => return Some((key, replace(&mut x.value, value))),
+        };
+        if old.is_none() {
+            // the tree below grew by one node
+            Node::rebalance(link);
+        }
+        old
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.remove_entry(key).map(|e| e.1)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the key
+    /// was previously in the map.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let entry = Self::remove_inner(&self.cmp, &mut self.root, key)?;
+        self.size -= 1;
+        Some(entry)
+    }
+
+    fn remove_inner<Q>(cmp: &C, link: &mut Link<K, V>, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let x = link.as_mut()?;
+        let removed = match cmp.compare(key, x.key.borrow()) {
+            Ordering::Less => Self::remove_inner(cmp, &mut x.left, key)?,
+            Ordering::Greater => Self::remove_inner(cmp, &mut x.right, key)?,
+            Ordering::Equal => {
+                let mut x = link.take().unwrap();
+                *link = match (x.left.take(), x.right.take()) {
+                    (None, right) => right,
+                    (left, None) => left,
+                    (left, Some(right)) => {
+                        // the successor takes the place of the removed node
+                        let (right, mut min) = Node::remove_min(right);
+                        min.left = left;
+                        min.right = right;
+                        Some(Node::balance(min))
+                    }
+                };
+                return Some((x.key, x.value));
+            }
+        };
+        Node::rebalance(link);
+        Some(removed)
+    }
+
+    /// Returns the first key-value pair in the map. The key in this pair is the minimum key in the map.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut x = self.root.as_deref()?;
+        while let Some(left) = x.left.as_deref() {
+            x = left;
+        }
+        Some((&x.key, &x.value))
+    }
+
+    /// Returns the last key-value pair in the map. The key in this pair is the maximum key in the map.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut x = self.root.as_deref()?;
+        while let Some(right) = x.right.as_deref() {
+            x = right;
+        }
+        Some((&x.key, &x.value))
+    }
+
+    /// Removes and returns the first element in the map. The key of this element is the minimum key that was in the map.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let (root, min) = Node::remove_min(self.root.take()?);
+        self.root = root;
+        self.size -= 1;
+        Some((min.key, min.value))
+    }
+
+    /// Removes and returns the last element in the map. The key of this element is the maximum key that was in the map.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let (root, last) = Node::remove_max(self.root.take()?);
+        self.root = root;
+        self.size -= 1;
+        Some((last.key, last.value))
+    }
+
+    /// Gets an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            front: Vec::new(),
+            back: Vec::new(),
+            length: self.size,
+        };
+        iter.push_left(self.root.as_deref());
+        iter.push_right(self.root.as_deref());
+        iter
+    }
+
+    /// Gets an iterator over the keys of the map, in sorted order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Gets an iterator over the values of the map, in order by key.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    fn search_node<Q>(&self, key: &Q) -> Option<&Node<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cur = self.root.as_deref();
+        while let Some(x) = cur {
+            match self.cmp.compare(key, x.key.borrow()) {
+                Ordering::Less => cur = x.left.as_deref(),
+                Ordering::Greater => cur = x.right.as_deref(),
+                Ordering::Equal => return Some(x),
+            }
+        }
+        None
+    }
+
+    fn search_node_mut<Q>(&mut self, key: &Q) -> Option<&mut Node<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cur = self.root.as_deref_mut();
+        while let Some(x) = cur {
+            match self.cmp.compare(key, x.key.borrow()) {
+                Ordering::Less => cur = x.left.as_deref_mut(),
+                Ordering::Greater => cur = x.right.as_deref_mut(),
+                Ordering::Equal => return Some(x),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+impl<K, V> AVLTreeMap<K, V> {
+    /// check the heights and the balance of every node under node,
+    /// returns the number of nodes
+    fn is_balanced(node: &Link<K, V>) -> Option<usize> {
+        match node {
+            None => Some(0),
+            Some(x) => {
+                let left = Self::is_balanced(&x.left)?;
+                let right = Self::is_balanced(&x.right)?;
+                let expected = max(Node::height(&x.left), Node::height(&x.right)) + 1;
+                if x.height != expected || !(-1..=1).contains(&x.balance_factor()) {
+                    return None;
+                }
+                Some(left + 1 + right)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl<K, V, C> AVLTreeMap<K, V, C> {
+    /// check that the tree is balanced and holds as many nodes as the map's size
+    pub(crate) fn is_valid(&self) -> bool {
+        AVLTreeMap::<K, V>::is_balanced(&self.root) == Some(self.size)
+    }
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut node: Option<&'a Node<K, V>>) {
+        while let Some(x) = node {
+            self.front.push(x);
+            node = x.left.as_deref();
+        }
+    }
+
+    fn push_right(&mut self, mut node: Option<&'a Node<K, V>>) {
+        while let Some(x) = node {
+            self.back.push(x);
+            node = x.right.as_deref();
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        let x = self.front.pop()?;
+        self.push_left(x.right.as_deref());
+        self.length -= 1;
+        Some((&x.key, &x.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        let x = self.back.pop()?;
+        self.push_right(x.left.as_deref());
+        self.length -= 1;
+        Some((&x.key, &x.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
+impl<K, V> IntoIter<K, V> {
+    fn push_left(&mut self, mut node: Link<K, V>) {
+        while let Some(mut x) = node {
+            node = x.left.take();
+            self.stack.push(x);
+        }
+    }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let mut x = self.stack.pop()?;
+        self.push_left(x.right.take());
+        self.length -= 1;
+        Some((x.key, x.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V, C> IntoIterator for AVLTreeMap<K, V, C> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        let mut iter = IntoIter {
+            stack: Vec::new(),
+            length: self.size,
+        };
+        iter.push_left(self.root.take());
+        iter
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a AVLTreeMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V, C: Default> Default for AVLTreeMap<K, V, C> {
+    /// Creates an empty `AVLTreeMap`, with the default comparator.
+    fn default() -> AVLTreeMap<K, V, C> {
+        AVLTreeMap::with_comparator(C::default())
+    }
+}
+
+impl<K: Debug, V: Debug, C> Debug for AVLTreeMap<K, V, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, C> PartialEq for AVLTreeMap<K, V, C> {
+    fn eq(&self, other: &AVLTreeMap<K, V, C>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C> Eq for AVLTreeMap<K, V, C> {}
+
+impl<K, Q, V, C> Index<&Q> for AVLTreeMap<K, V, C>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `AVLTreeMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for AVLTreeMap<K, V, C> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> AVLTreeMap<K, V, C> {
+        let mut map = AVLTreeMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, C: Comparator<K>> Extend<(K, V)> for AVLTreeMap<K, V, C> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AVLTreeMap;
+    use std::collections::BTreeMap;
+
+    #[quickcheck]
+    fn insert_and_remove(v: Vec<i16>, w: Vec<i16>) -> bool {
+        let mut map = AVLTreeMap::new();
+        for &x in v.iter() {
+            map.insert(x, x);
+            if AVLTreeMap::is_balanced(&map.root) != Some(map.len()) {
+                return false;
+            }
+        }
+        for &x in w.iter().chain(v.iter()) {
+            map.remove(&x);
+            if AVLTreeMap::is_balanced(&map.root) != Some(map.len()) {
+                return false;
+            }
+        }
+        map.is_empty()
+    }
+
+    #[quickcheck]
+    fn pop_first_and_last(v: Vec<i16>, from_last: Vec<bool>) -> bool {
+        let mut map: AVLTreeMap<i16, ()> = v.iter().map(|&x| (x, ())).collect();
+        let mut expected: BTreeMap<i16, ()> = v.iter().map(|&x| (x, ())).collect();
+        for last in from_last.into_iter().chain(v.iter().map(|_| false)) {
+            let popped = if last {
+                (map.pop_last(), expected.pop_last())
+            } else {
+                (map.pop_first(), expected.pop_first())
+            };
+            if popped.0 != popped.1 || AVLTreeMap::is_balanced(&map.root) != Some(map.len()) {
+                return false;
+            }
+        }
+        map.is_empty()
+    }
+}
@@ -0,0 +1,34 @@
+use std::cmp::Ordering;
+
+/// A total order on values of type `T`, used by the trees to order their keys
+/// instead of the `Ord` implementation of the key type.
+///
+/// This allows orders such as case-insensitive strings, reversed keys or floats
+/// compared with [`f64::total_cmp`] without wrapping the keys in a newtype.
+/// Closures of type `Fn(&T, &T) -> Ordering` are comparators.
+///
+/// A map looking up keys by a borrowed form `Q` of its key type compares them
+/// with `Comparator<Q>`, which must agree with `Comparator<K>` on the keys.
+pub trait Comparator<T: ?Sized> {
+    /// Returns the ordering of `a` with respect to `b`.
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/// The default comparator, ordering values by their `Ord` implementation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrdComparator;
+
+impl<T: ?Sized + Ord> Comparator<T> for OrdComparator {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T: ?Sized, F> Comparator<T> for F
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
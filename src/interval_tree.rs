@@ -0,0 +1,406 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem::replace;
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+use std::ptr;
+
+use crate::comparator::Comparator;
+use crate::rbtree::{self, Entry, Node, RBTreeMap};
+
+// Orders intervals by start and then by end, the order of the tree.
+struct IntervalOrder;
+
+// The value of an interval, stored in the red-black tree along with the node
+// with the greatest end in the subtree rooted at the node holding it.
+struct Augmented<K, V> {
+    value: V,
+    max: *mut IntervalNode<K, V>,
+}
+
+type IntervalNode<K, V> = Node<RangeInclusive<K>, Augmented<K, V>>;
+
+/// A map from closed intervals `start..=end` to values, stored in a red-black tree
+/// ordered by start and then by end.
+///
+/// Each node also records the greatest end in its subtree, which lets
+/// [`overlapping`] and [`stabbing`] skip the subtrees that end before the query
+/// starts. Reporting the k matching intervals takes O(min(n, (k + 1) log n)) time.
+///
+/// [`overlapping`]: IntervalTreeMap::overlapping
+/// [`stabbing`]: IntervalTreeMap::stabbing
+pub struct IntervalTreeMap<K, V> {
+    // the rotations and fixups of the tree keep the max of every node up to
+    // date through `update_max`
+    map: RBTreeMap<RangeInclusive<K>, Augmented<K, V>, IntervalOrder>,
+}
+
+// An iterator over the entries of a IntervalTreeMap.
+pub struct Iter<'a, K: 'a, V: 'a> {
+    inner: rbtree::Iter<'a, RangeInclusive<K>, Augmented<K, V>>,
+}
+
+/// An iterator over the entries of a `IntervalTreeMap` whose intervals overlap a range,
+/// in order of their starts.
+///
+/// This `struct` is created by the [`overlapping`] method on [`IntervalTreeMap`].
+///
+/// [`overlapping`]: IntervalTreeMap::overlapping
+pub struct Overlapping<'a, K: 'a, V: 'a, R> {
+    // nodes still to be visited along with their right subtrees, the next one on top
+    stack: Vec<*mut IntervalNode<K, V>>,
+    range: R,
+    phantom: PhantomData<(&'a RangeInclusive<K>, &'a V)>,
+}
+
+/// An iterator over the entries of a `IntervalTreeMap` whose intervals contain a point,
+/// in order of their starts.
+///
+/// This `struct` is created by the [`stabbing`] method on [`IntervalTreeMap`].
+///
+/// [`stabbing`]: IntervalTreeMap::stabbing
+pub struct Stabbing<'a, K: 'a, V: 'a> {
+    inner: Overlapping<'a, K, V, (Bound<&'a K>, Bound<&'a K>)>,
+}
+
+impl<K: Ord> Comparator<RangeInclusive<K>> for IntervalOrder {
+    fn compare(&self, a: &RangeInclusive<K>, b: &RangeInclusive<K>) -> Ordering {
+        a.start().cmp(b.start()).then_with(|| a.end().cmp(b.end()))
+    }
+}
+
+// Returns the greatest end in the subtree rooted at node, which must not be null.
+unsafe fn max_end<'a, K, V>(node: *mut IntervalNode<K, V>) -> &'a K {
+    (*(*node).value.max).key.end()
+}
+
+// Recomputes the max of node from its own interval and the max of its children.
+unsafe fn update_max<K: Ord, V>(node: *mut IntervalNode<K, V>) {
+    let mut max = node;
+    for child in [(*node).left, (*node).right] {
+        if !child.is_null() && max_end(child) > (*max).key.end() {
+            max = (*child).value.max;
+        }
+    }
+    (*node).value.max = max;
+}
+
+// Returns `true` if an interval ending at `end` reaches the start of the range.
+fn reaches_start<K: Ord, R: RangeBounds<K>>(range: &R, end: &K) -> bool {
+    match range.start_bound() {
+        Bound::Included(s) => end >= s,
+        Bound::Excluded(s) => end > s,
+        Bound::Unbounded => true,
+    }
+}
+
+// Returns `true` if an interval starting at `start` begins before the end of the range.
+fn starts_before_end<K: Ord, R: RangeBounds<K>>(range: &R, start: &K) -> bool {
+    match range.end_bound() {
+        Bound::Included(e) => start <= e,
+        Bound::Excluded(e) => start < e,
+        Bound::Unbounded => true,
+    }
+}
+
+impl<K: Ord, V> IntervalTreeMap<K, V> {
+    /// Makes a new, empty `IntervalTreeMap`.
+    pub fn new() -> IntervalTreeMap<K, V> {
+        IntervalTreeMap {
+            map: RBTreeMap::with_augment(IntervalOrder, update_max::<K, V>),
+        }
+    }
+
+    /// Returns the number of intervals in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no intervals.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Clears the map, removing all intervals.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Returns the value stored for exactly the interval `range`.
+    pub fn get(&self, range: &RangeInclusive<K>) -> Option<&V> {
+        self.map.get(range).map(|a| &a.value)
+    }
+
+    /// Returns a mutable reference to the value stored for exactly the interval `range`.
+    pub fn get_mut(&mut self, range: &RangeInclusive<K>) -> Option<&mut V> {
+        self.map.get_mut(range).map(|a| &mut a.value)
+    }
+
+    /// Returns `true` if the map contains a value for exactly the interval `range`.
+    pub fn contains_key(&self, range: &RangeInclusive<K>) -> bool {
+        self.map.contains_key(range)
+    }
+
+    /// Inserts an interval and its value into the map.
+    ///
+    /// If the map did not have this interval present, `None` is returned.
+    ///
+    /// If the map did have this interval present, the value is updated, and the old
+    /// value is returned. Intervals are equal when both their starts and their ends are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the interval is greater than its end.
+    pub fn insert(&mut self, range: RangeInclusive<K>, value: V) -> Option<V> {
+        assert!(
+            range.start() <= range.end(),
+            "interval start is greater than its end in IntervalTreeMap"
+        );
+
+        match self.map.entry(range) {
+            Entry::Occupied(mut entry) => Some(replace(&mut entry.get_mut().value, value)),
+            Entry::Vacant(entry) => {
+                // the max is set by update_max once the node is linked
+                entry.insert(Augmented {
+                    value,
+                    max: ptr::null_mut(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Removes exactly the interval `range` from the map, returning its value if
+    /// the interval was previously in the map.
+    pub fn remove(&mut self, range: &RangeInclusive<K>) -> Option<V> {
+        self.remove_entry(range).map(|(_, v)| v)
+    }
+
+    /// Removes exactly the interval `range` from the map, returning the stored
+    /// interval and value if the interval was previously in the map.
+    pub fn remove_entry(&mut self, range: &RangeInclusive<K>) -> Option<(RangeInclusive<K>, V)> {
+        self.map.remove_entry(range).map(|(k, a)| (k, a.value))
+    }
+
+    /// Gets an iterator over the entries of the map, sorted by start and then by end.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.map.iter(),
+        }
+    }
+
+    /// Gets an iterator over the entries whose intervals overlap `range`, that is
+    /// share at least one point with it, sorted by start and then by end.
+    ///
+    /// Intervals are closed, so `2..=4` overlaps `4..=6` and `..=2`, but not `(Excluded(4), Unbounded)`.
+    pub fn overlapping<R: RangeBounds<K>>(&self, range: R) -> Overlapping<'_, K, V, R> {
+        let mut iter = Overlapping {
+            stack: Vec::new(),
+            range,
+            phantom: PhantomData,
+        };
+        unsafe { iter.descend(self.map.root_node()) };
+        iter
+    }
+
+    /// Gets an iterator over the entries whose intervals contain `point`,
+    /// sorted by start and then by end.
+    pub fn stabbing<'a>(&'a self, point: &'a K) -> Stabbing<'a, K, V> {
+        Stabbing {
+            inner: self.overlapping((Bound::Included(point), Bound::Included(point))),
+        }
+    }
+
+    /// check if the max of every node under node is consistent,
+    /// returns a node with the greatest end in the tree rooted at node
+    #[cfg(test)]
+    fn check_max(node: *mut IntervalNode<K, V>) -> Option<*mut IntervalNode<K, V>> {
+        if node.is_null() {
+            return Some(node);
+        }
+        unsafe {
+            let mut max = node;
+            for child in [(*node).left, (*node).right] {
+                if child.is_null() {
+                    continue;
+                }
+                let m = Self::check_max(child)?;
+                if (*m).key.end() > (*max).key.end() {
+                    max = m;
+                }
+            }
+            if max_end(node) != (*max).key.end() {
+                return None;
+            }
+            Some(max)
+        }
+    }
+}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>> Overlapping<'a, K, V, R> {
+    // Pushes node and the left spine below it, stopping at the first subtree
+    // whose intervals all end before the range starts.
+    unsafe fn descend(&mut self, node: *mut IntervalNode<K, V>) {
+        let mut x = node;
+        while !x.is_null() && reaches_start(&self.range, max_end(x)) {
+            self.stack.push(x);
+            x = (*x).left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a RangeInclusive<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, a)| (k, &a.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>> Iterator for Overlapping<'a, K, V, R> {
+    type Item = (&'a RangeInclusive<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            while let Some(x) = self.stack.pop() {
+                if !starts_before_end(&self.range, (*x).key.start()) {
+                    // the nodes left on the stack and their right subtrees
+                    // start no earlier than x
+                    self.stack.clear();
+                    return None;
+                }
+                self.descend((*x).right);
+                if reaches_start(&self.range, (*x).key.end()) {
+                    return Some((&(*x).key, &(*x).value.value));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>> FusedIterator for Overlapping<'a, K, V, R> {}
+
+impl<'a, K: Ord, V> Iterator for Stabbing<'a, K, V> {
+    type Item = (&'a RangeInclusive<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, K: Ord, V> FusedIterator for Stabbing<'a, K, V> {}
+
+impl<'a, K: Ord, V> IntoIterator for &'a IntervalTreeMap<K, V> {
+    type Item = (&'a RangeInclusive<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V> Default for IntervalTreeMap<K, V> {
+    /// Creates an empty `IntervalTreeMap`.
+    fn default() -> IntervalTreeMap<K, V> {
+        IntervalTreeMap::new()
+    }
+}
+
+impl<K: Ord + Debug, V: Debug> Debug for IntervalTreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(RangeInclusive<K>, V)> for IntervalTreeMap<K, V> {
+    fn from_iter<T: IntoIterator<Item = (RangeInclusive<K>, V)>>(iter: T) -> Self {
+        let mut map = IntervalTreeMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(RangeInclusive<K>, V)> for IntervalTreeMap<K, V> {
+    fn extend<T: IntoIterator<Item = (RangeInclusive<K>, V)>>(&mut self, iter: T) {
+        for (range, v) in iter {
+            self.insert(range, v);
+        }
+    }
+}
+
+// The max pointers opt the map and its iterators out of Send and Sync. The map
+// owns its nodes like a Box would, and the iterators stand for shared borrows
+// of the map, so they are given the bounds of what they stand for.
+unsafe impl<K: Send, V: Send> Send for IntervalTreeMap<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for IntervalTreeMap<K, V> {}
+
+unsafe impl<K: Sync, V: Sync> Send for Iter<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Iter<'_, K, V> {}
+
+unsafe impl<K: Sync, V: Sync, R: Send> Send for Overlapping<'_, K, V, R> {}
+unsafe impl<K: Sync, V: Sync, R: Sync> Sync for Overlapping<'_, K, V, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Augmented, IntervalTreeMap};
+    use crate::rbtree::RBTreeMap;
+    use std::ops::RangeInclusive;
+
+    type Tree = RBTreeMap<RangeInclusive<i32>, Augmented<i32, i32>>;
+
+    fn is_valid(map: &IntervalTreeMap<i32, i32>) -> bool {
+        let root = map.map.root_node();
+        Tree::is_234tree(root).is_some()
+            && Tree::check_links(root) == Some(map.len())
+            && IntervalTreeMap::check_max(root).is_some()
+    }
+
+    #[quickcheck]
+    fn insert_and_remove(v: Vec<(i32, u8)>) -> bool {
+        let mut map = IntervalTreeMap::new();
+
+        for &(start, len) in v.iter() {
+            let end = start.saturating_add(len as i32);
+            map.insert(start..=end, start);
+            if !is_valid(&map) {
+                return false;
+            }
+        }
+        for &(start, len) in v.iter() {
+            let end = start.saturating_add(len as i32);
+            map.remove(&(start..=end));
+            if !is_valid(&map) {
+                return false;
+            }
+        }
+
+        map.is_empty()
+    }
+
+    #[quickcheck]
+    fn remove_in_any_order(v: Vec<(i8, u8)>, w: Vec<(i8, u8)>) -> bool {
+        let mut map = IntervalTreeMap::new();
+        for &(start, len) in v.iter() {
+            let start = start as i32;
+            map.insert(start..=start + len as i32, 0);
+        }
+        for &(start, len) in w.iter().chain(v.iter().rev()) {
+            let start = start as i32;
+            map.remove(&(start..=start + len as i32));
+            if !is_valid(&map) {
+                return false;
+            }
+        }
+        map.is_empty()
+    }
+}
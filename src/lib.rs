@@ -1,7 +1,13 @@
+pub mod arena_rbtree;
 pub mod avltree;
+pub mod avltree_map;
+pub mod comparator;
+pub mod interval_tree;
+pub mod persistent_avltree;
 pub mod rbtree;
+pub mod rbtree_set;
 #[cfg(test)]
 extern crate quickcheck;
 #[cfg(test)]
 #[macro_use(quickcheck)]
-extern crate quickcheck_macros;
\ No newline at end of file
+extern crate quickcheck_macros;
@@ -0,0 +1,633 @@
+use std::borrow::Borrow;
+use std::cmp::{max, Ordering};
+use std::fmt::{self, Debug};
+use std::iter::FusedIterator;
+use std::ptr;
+use std::rc::Rc;
+
+#[derive(Clone)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+    height: i32,
+}
+
+type Link<K, V> = Option<Rc<Node<K, V>>>;
+
+/// A persistent map based on an AVL tree.
+///
+/// Nodes are immutable and shared between versions through `Rc`. [`insert`] and
+/// [`remove`] leave `self` untouched and return a new version, which copies only
+/// the O(log n) nodes on the path to the key and shares every other subtree with
+/// `self`. Cloning a version is O(1), and [`diff`] skips the subtrees two versions
+/// still share, so it costs about O(d log n) for d differences.
+///
+/// [`insert`]: PersistentAVLTree::insert
+/// [`remove`]: PersistentAVLTree::remove
+/// [`diff`]: PersistentAVLTree::diff
+pub struct PersistentAVLTree<K, V> {
+    root: Link<K, V>,
+    size: usize,
+}
+
+/// An iterator over the entries of a `PersistentAVLTree`, sorted by key.
+///
+/// This `struct` is created by the [`iter`] method on [`PersistentAVLTree`].
+///
+/// [`iter`]: PersistentAVLTree::iter
+pub struct Iter<'a, K, V> {
+    // nodes whose left subtree has been visited, the next one on top
+    stack: Vec<&'a Node<K, V>>,
+    length: usize,
+}
+
+/// A difference between two versions of a `PersistentAVLTree`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DiffItem<'a, K, V> {
+    /// An entry of the first version whose key is not in the second.
+    Removed(&'a K, &'a V),
+    /// An entry of the second version whose key is not in the first.
+    Added(&'a K, &'a V),
+    /// A key of both versions with different values, the old one first.
+    Changed(&'a K, &'a V, &'a V),
+}
+
+/// An iterator over the differences between two versions of a `PersistentAVLTree`,
+/// sorted by key.
+///
+/// This `struct` is created by the [`diff`] method on [`PersistentAVLTree`].
+///
+/// [`diff`]: PersistentAVLTree::diff
+pub struct Diff<'a, K, V> {
+    old: Vec<Pending<'a, K, V>>,
+    new: Vec<Pending<'a, K, V>>,
+}
+
+// What is left to compare on one side of a diff, the next one on top of the stack:
+// a whole subtree, or a single entry whose left subtree has been compared.
+enum Pending<'a, K, V> {
+    Tree(&'a Rc<Node<K, V>>),
+    Entry(&'a Node<K, V>),
+}
+
+impl<K, V> Clone for Pending<'_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for Pending<'_, K, V> {}
+
+impl<K, V> Node<K, V> {
+    fn new(key: K, value: V, left: Link<K, V>, right: Link<K, V>) -> Rc<Node<K, V>> {
+        let height = max(Self::height(&left), Self::height(&right)) + 1;
+        Rc::new(Node {
+            key,
+            value,
+            left,
+            right,
+            height,
+        })
+    }
+
+    fn height(link: &Link<K, V>) -> i32 {
+        link.as_ref().map_or(0, |x| x.height)
+    }
+}
+
+impl<K: Clone, V: Clone> Node<K, V> {
+    // Makes a node from an entry and two subtrees whose heights differ by at most 2,
+    // rotating when they differ by 2. Nodes taken apart by a rotation are only
+    // copied when another version still shares them.
+    fn balance(key: K, value: V, left: Link<K, V>, right: Link<K, V>) -> Rc<Node<K, V>> {
+        let lh = Self::height(&left);
+        let rh = Self::height(&right);
+        if lh > rh + 1 {
+            let l = Rc::unwrap_or_clone(left.unwrap());
+            if Self::height(&l.right) > Self::height(&l.left) {
+                let lr = Rc::unwrap_or_clone(l.right.unwrap());
+                Self::new(
+                    lr.key,
+                    lr.value,
+                    Some(Self::new(l.key, l.value, l.left, lr.left)),
+                    Some(Self::new(key, value, lr.right, right)),
+                )
+            } else {
+                Self::new(
+                    l.key,
+                    l.value,
+                    l.left,
+                    Some(Self::new(key, value, l.right, right)),
+                )
+            }
+        } else if rh > lh + 1 {
+            let r = Rc::unwrap_or_clone(right.unwrap());
+            if Self::height(&r.left) > Self::height(&r.right) {
+                let rl = Rc::unwrap_or_clone(r.left.unwrap());
+                Self::new(
+                    rl.key,
+                    rl.value,
+                    Some(Self::new(key, value, left, rl.left)),
+                    Some(Self::new(r.key, r.value, rl.right, r.right)),
+                )
+            } else {
+                Self::new(
+                    r.key,
+                    r.value,
+                    Some(Self::new(key, value, left, r.left)),
+                    r.right,
+                )
+            }
+        } else {
+            Self::new(key, value, left, right)
+        }
+    }
+
+    // Returns a copy of the subtree at link with the entry inserted, and whether
+    // the key is new.
+    fn insert(link: &Link<K, V>, key: K, value: V) -> (Rc<Node<K, V>>, bool)
+    where
+        K: Ord,
+    {
+        let Some(node) = link else {
+            return (Self::new(key, value, None, None), true);
+        };
+        match key.cmp(&node.key) {
+            Ordering::Less => {
+                let (left, added) = Self::insert(&node.left, key, value);
+                let x = Self::balance(
+                    node.key.clone(),
+                    node.value.clone(),
+                    Some(left),
+                    node.right.clone(),
+                );
+                (x, added)
+            }
+            Ordering::Greater => {
+                let (right, added) = Self::insert(&node.right, key, value);
+                let x = Self::balance(
+                    node.key.clone(),
+                    node.value.clone(),
+                    node.left.clone(),
+                    Some(right),
+                );
+                (x, added)
+            }
+            Ordering::Equal => (
+                Self::new(
+                    node.key.clone(),
+                    value,
+                    node.left.clone(),
+                    node.right.clone(),
+                ),
+                false,
+            ),
+        }
+    }
+
+    // Returns a copy of the subtree at link without key,
+    // or `None` if the key is not in the subtree.
+    fn remove<Q>(link: &Link<K, V>, key: &Q) -> Option<Link<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = link.as_ref()?;
+        let x = match key.cmp(node.key.borrow()) {
+            Ordering::Less => {
+                let left = Self::remove(&node.left, key)?;
+                Self::balance(
+                    node.key.clone(),
+                    node.value.clone(),
+                    left,
+                    node.right.clone(),
+                )
+            }
+            Ordering::Greater => {
+                let right = Self::remove(&node.right, key)?;
+                Self::balance(
+                    node.key.clone(),
+                    node.value.clone(),
+                    node.left.clone(),
+                    right,
+                )
+            }
+            Ordering::Equal => match (&node.left, &node.right) {
+                (None, right) => return Some(right.clone()),
+                (left, None) => return Some(left.clone()),
+                (left, Some(right)) => {
+                    let (right, min) = Self::remove_min(right);
+                    Self::balance(min.key.clone(), min.value.clone(), left.clone(), right)
+                }
+            },
+        };
+        Some(Some(x))
+    }
+
+    // Returns a copy of the subtree rooted at node without its first entry,
+    // along with the node holding that entry.
+    fn remove_min(node: &Rc<Node<K, V>>) -> (Link<K, V>, &Node<K, V>) {
+        match &node.left {
+            None => (node.right.clone(), node),
+            Some(left) => {
+                let (left, min) = Self::remove_min(left);
+                let x = Self::balance(
+                    node.key.clone(),
+                    node.value.clone(),
+                    left,
+                    node.right.clone(),
+                );
+                (Some(x), min)
+            }
+        }
+    }
+}
+
+impl<K, V> PersistentAVLTree<K, V> {
+    /// Makes a new, empty `PersistentAVLTree`.
+    pub fn new() -> Self {
+        PersistentAVLTree {
+            root: None,
+            size: 0,
+        }
+    }
+
+    /// Returns the number of entries in this version.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if this version contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns `true` if the two versions share their whole tree, as clones of
+    /// each other do. Versions with equal contents built separately may not.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    /// Gets an iterator over the entries of this version, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            length: self.size,
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+impl<K: Ord, V> PersistentAVLTree<K, V> {
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cur = &self.root;
+        while let Some(x) = cur {
+            match key.cmp(x.key.borrow()) {
+                Ordering::Less => cur = &x.left,
+                Ordering::Greater => cur = &x.right,
+                Ordering::Equal => return Some(&x.value),
+            }
+        }
+        None
+    }
+
+    /// Returns `true` if this version contains a value for the specified key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentAVLTree<K, V> {
+    /// Returns a new version with the entry inserted, replacing the value if the key
+    /// is already present. The key itself is not updated.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let (root, added) = Node::insert(&self.root, key, value);
+        PersistentAVLTree {
+            root: Some(root),
+            size: self.size + added as usize,
+        }
+    }
+
+    /// Returns a new version without the key. If the key is not present, the new
+    /// version shares the whole tree with `self`.
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match Node::remove(&self.root, key) {
+            Some(root) => PersistentAVLTree {
+                root,
+                size: self.size - 1,
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> PersistentAVLTree<K, V> {
+    /// Gets an iterator over the differences from `self` to `other`, sorted by key.
+    ///
+    /// Subtrees shared by both versions are skipped without being visited, so
+    /// comparing a version with one derived from it by a few edits is cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use dsgym_rs::persistent_avltree::{DiffItem, PersistentAVLTree};
+    ///
+    /// let v1: PersistentAVLTree<i32, &str> = (0..1000).map(|x| (x, "a")).collect();
+    /// let v2 = v1.insert(10, "b").remove(&20).insert(1000, "c");
+    ///
+    /// let diff: Vec<_> = v1.diff(&v2).collect();
+    /// assert_eq!(
+    ///     diff,
+    ///     [
+    ///         DiffItem::Changed(&10, &"a", &"b"),
+    ///         DiffItem::Removed(&20, &"a"),
+    ///         DiffItem::Added(&1000, &"c"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a Self) -> Diff<'a, K, V> {
+        let mut diff = Diff {
+            old: Vec::new(),
+            new: Vec::new(),
+        };
+        Diff::push_tree(&mut diff.old, &self.root);
+        Diff::push_tree(&mut diff.new, &other.root);
+        diff
+    }
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn push_left(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(x) = link {
+            self.stack.push(x);
+            link = &x.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.stack.pop()?;
+        self.push_left(&x.right);
+        self.length -= 1;
+        Some((&x.key, &x.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V> Diff<'a, K, V> {
+    fn push_tree(stack: &mut Vec<Pending<'a, K, V>>, link: &'a Link<K, V>) {
+        if let Some(x) = link {
+            stack.push(Pending::Tree(x));
+        }
+    }
+
+    // Replaces the subtree on top of the stack with its left subtree, root and
+    // right subtree.
+    fn expand(stack: &mut Vec<Pending<'a, K, V>>) {
+        if let Some(Pending::Tree(x)) = stack.pop() {
+            Self::push_tree(stack, &x.right);
+            stack.push(Pending::Entry(x));
+            Self::push_tree(stack, &x.left);
+        }
+    }
+}
+
+impl<'a, K: Ord, V: PartialEq> Iterator for Diff<'a, K, V> {
+    type Item = DiffItem<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match (self.old.last().copied(), self.new.last().copied()) {
+                (None, None) => return None,
+                (Some(Pending::Tree(a)), Some(Pending::Tree(b))) => {
+                    if Rc::ptr_eq(a, b) {
+                        // the remaining entries of both start with the same subtree
+                        self.old.pop();
+                        self.new.pop();
+                    } else if a.height >= b.height {
+                        Self::expand(&mut self.old);
+                    } else {
+                        Self::expand(&mut self.new);
+                    }
+                }
+                (Some(Pending::Tree(_)), _) => Self::expand(&mut self.old),
+                (_, Some(Pending::Tree(_))) => Self::expand(&mut self.new),
+                (Some(Pending::Entry(a)), Some(Pending::Entry(b))) => match a.key.cmp(&b.key) {
+                    Ordering::Less => {
+                        self.old.pop();
+                        return Some(DiffItem::Removed(&a.key, &a.value));
+                    }
+                    Ordering::Greater => {
+                        self.new.pop();
+                        return Some(DiffItem::Added(&b.key, &b.value));
+                    }
+                    Ordering::Equal => {
+                        self.old.pop();
+                        self.new.pop();
+                        if !ptr::eq(a, b) && a.value != b.value {
+                            return Some(DiffItem::Changed(&a.key, &a.value, &b.value));
+                        }
+                    }
+                },
+                (Some(Pending::Entry(a)), None) => {
+                    self.old.pop();
+                    return Some(DiffItem::Removed(&a.key, &a.value));
+                }
+                (None, Some(Pending::Entry(b))) => {
+                    self.new.pop();
+                    return Some(DiffItem::Added(&b.key, &b.value));
+                }
+            }
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> FusedIterator for Diff<'_, K, V> {}
+
+impl<K, V> Clone for PersistentAVLTree<K, V> {
+    /// Returns a version sharing the whole tree with `self` in O(1) time.
+    fn clone(&self) -> Self {
+        PersistentAVLTree {
+            root: self.root.clone(),
+            size: self.size,
+        }
+    }
+}
+
+impl<K, V> Default for PersistentAVLTree<K, V> {
+    /// Creates an empty `PersistentAVLTree`.
+    fn default() -> Self {
+        PersistentAVLTree::new()
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for PersistentAVLTree<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq> PartialEq for PersistentAVLTree<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || (self.len() == other.len() && self.iter().eq(other.iter()))
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for PersistentAVLTree<K, V> {}
+
+impl<'a, K, V> IntoIterator for &'a PersistentAVLTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FromIterator<(K, V)> for PersistentAVLTree<K, V> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        iter.into_iter()
+            .fold(PersistentAVLTree::new(), |tree, (k, v)| tree.insert(k, v))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DiffItem, Link, Node, PersistentAVLTree};
+    use std::collections::BTreeMap;
+    use std::rc::Rc;
+
+    fn is_balanced<K: Ord, V>(link: &Link<K, V>) -> bool {
+        match link {
+            Some(x) => {
+                let lh = Node::height(&x.left);
+                let rh = Node::height(&x.right);
+                x.left.as_ref().is_none_or(|l| l.key < x.key)
+                    && x.right.as_ref().is_none_or(|r| r.key > x.key)
+                    && is_balanced(&x.left)
+                    && is_balanced(&x.right)
+                    && x.height == lh.max(rh) + 1
+                    && (-1..=1).contains(&(lh - rh))
+            }
+            None => true,
+        }
+    }
+
+    #[test]
+    fn versions() {
+        let v0 = PersistentAVLTree::new();
+        let v1 = v0.insert(1, "a").insert(2, "b").insert(3, "c");
+        let v2 = v1.insert(2, "B");
+        let v3 = v2.remove(&1);
+        let v4 = v3.remove(&1);
+
+        assert!(v0.is_empty());
+        assert_eq!(v1.len(), 3);
+        assert_eq!(v1.get(&2), Some(&"b"));
+        assert_eq!(v2.get(&2), Some(&"B"));
+        assert_eq!(v2.len(), 3);
+        assert!(v2.contains_key(&1));
+        assert!(!v3.contains_key(&1));
+        assert_eq!(v3.len(), 2);
+        assert!(v4.ptr_eq(&v3));
+        assert!(!v2.ptr_eq(&v1));
+        assert_eq!(v1.clone(), v1);
+        assert_eq!(format!("{:?}", v3), r#"{2: "B", 3: "c"}"#);
+    }
+
+    #[test]
+    fn shares_unchanged_subtrees() {
+        let v1: PersistentAVLTree<i32, i32> = (0..1000).map(|x| (x, x)).collect();
+        let v2 = v1.insert(500, 0);
+        // only the path to the key is copied
+        let shared = |a: &Link<i32, i32>, b: &Link<i32, i32>| match (a, b) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        };
+        let (r1, r2) = (v1.root.as_ref().unwrap(), v2.root.as_ref().unwrap());
+        assert!(!Rc::ptr_eq(r1, r2));
+        assert!(shared(&r1.left, &r2.left) != shared(&r1.right, &r2.right));
+        assert_eq!(v1.diff(&v2).count(), 1);
+        assert_eq!(v1.diff(&v1.clone()).count(), 0);
+    }
+
+    #[quickcheck]
+    fn insert_and_remove(v: Vec<(u8, u8)>, w: Vec<u8>) -> bool {
+        let mut versions = vec![(PersistentAVLTree::new(), BTreeMap::new())];
+        for &(k, x) in v.iter() {
+            let (tree, map) = versions.last().unwrap();
+            let mut map = map.clone();
+            map.insert(k, x);
+            versions.push((tree.insert(k, x), map));
+        }
+        for k in w.iter().chain(v.iter().map(|(k, _)| k)) {
+            let (tree, map) = versions.last().unwrap();
+            let mut map = map.clone();
+            map.remove(k);
+            versions.push((tree.remove(k), map));
+        }
+        // every version keeps its own contents
+        versions.iter().all(|(tree, map)| {
+            is_balanced(&tree.root) && tree.len() == map.len() && tree.iter().eq(map.iter())
+        })
+    }
+
+    #[quickcheck]
+    fn diff_like_btreemap(v: Vec<(u8, bool)>, edits: Vec<(u8, bool)>) -> bool {
+        let old: PersistentAVLTree<u8, bool> = v.into_iter().collect();
+        let mut new = old.clone();
+        for (k, x) in edits {
+            new = if x {
+                new.insert(k, k % 2 == 0)
+            } else {
+                new.remove(&k)
+            };
+        }
+
+        let a: BTreeMap<_, _> = old.iter().collect();
+        let b: BTreeMap<_, _> = new.iter().collect();
+        let mut expected = Vec::new();
+        for k in a
+            .keys()
+            .chain(b.keys())
+            .collect::<std::collections::BTreeSet<_>>()
+        {
+            match (a.get(k), b.get(k)) {
+                (Some(x), None) => expected.push(DiffItem::Removed(*k, *x)),
+                (None, Some(y)) => expected.push(DiffItem::Added(*k, *y)),
+                (Some(x), Some(y)) if x != y => expected.push(DiffItem::Changed(*k, *x, *y)),
+                _ => {}
+            }
+        }
+        old.diff(&new).eq(expected)
+    }
+}
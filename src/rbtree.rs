@@ -1,10 +1,17 @@
+use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::fmt::Display;
-use std::mem::{replace, swap};
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem::{replace, swap, take};
+use std::ops::{Bound, Index, RangeBounds};
 use std::ptr;
 
+use crate::comparator::{Comparator, OrdComparator};
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Color {
+pub(crate) enum Color {
     Red,
     Black,
 }
@@ -15,43 +22,188 @@ enum Direction {
 }
 
 #[derive(Debug)]
-struct Node<K, V> {
-    key: K,
-    value: V,
-    left: *mut Node<K, V>,
-    right: *mut Node<K, V>,
+pub(crate) struct Node<K, V> {
+    pub(crate) key: K,
+    pub(crate) value: V,
+    pub(crate) left: *mut Node<K, V>,
+    pub(crate) right: *mut Node<K, V>,
     parent: *mut Node<K, V>,
     color: Color,
+    // number of nodes in the subtree rooted at this node
+    size: usize,
 }
 
-pub struct RBTreeMap<K, V> {
+pub struct RBTreeMap<K, V, C = OrdComparator> {
     size: usize,
     root: *mut Node<K, V>,
+    cmp: C,
+    // Recomputes extra data kept in the value of a node from its children, for
+    // trees built on this one such as `IntervalTreeMap`. Insertions, removals
+    // and rotations call it wherever a subtree changes. Split, append, join and
+    // clone do not maintain it, and debug builds assert that they are never
+    // called on an augmented tree.
+    augment: Option<unsafe fn(*mut Node<K, V>)>,
+}
+
+// The root of a detached subtree and its black height, used by split and join.
+type Subtree<K, V> = (*mut Node<K, V>, usize);
+
+// In-order traversal between two nodes following parent pointers, shared by the
+// map iterators. `front` and `back` are the next nodes to be visited from either
+// end; both are null once the two ends have met.
+struct RawIter<K, V> {
+    front: *mut Node<K, V>,
+    back: *mut Node<K, V>,
 }
 
 // An iterator over the entries of a RBTreeMap.
 pub struct Iter<'a, K: 'a, V: 'a> {
-    stack: Vec<*mut Node<K, V>>,
-    phantom: *mut &'a Node<K, V>,
+    raw: RawIter<K, V>,
+    length: usize,
+    phantom: PhantomData<(&'a K, &'a V)>,
+}
+
+// A mutable iterator over the entries of a RBTreeMap.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    raw: RawIter<K, V>,
+    length: usize,
+    phantom: PhantomData<(&'a K, &'a mut V)>,
+}
+
+// An iterator over the keys of a RBTreeMap.
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+// An iterator over the values of a RBTreeMap.
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+// A mutable iterator over the values of a RBTreeMap.
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    inner: IterMut<'a, K, V>,
+}
+
+// An owning iterator over the entries of a RBTreeMap.
+// Nodes are unlinked from the remaining tree and freed as they are visited.
+pub struct IntoIter<K, V> {
+    // leftmost node of the remaining tree, its left subtree is already freed
+    front: *mut Node<K, V>,
+    // rightmost node of the remaining tree, its right subtree is already freed
+    back: *mut Node<K, V>,
+    length: usize,
+}
+
+// An owning iterator over the keys of a RBTreeMap.
+pub struct IntoKeys<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+// An owning iterator over the values of a RBTreeMap.
+pub struct IntoValues<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+// An iterator over a sub-range of entries in a RBTreeMap.
+pub struct Range<'a, K: 'a, V: 'a> {
+    raw: RawIter<K, V>,
+    phantom: PhantomData<(&'a K, &'a V)>,
+}
+
+// A mutable iterator over a sub-range of entries in a RBTreeMap.
+pub struct RangeMut<'a, K: 'a, V: 'a> {
+    raw: RawIter<K, V>,
+    phantom: PhantomData<(&'a K, &'a mut V)>,
+}
+
+/// An iterator produced by calling `extract_if` on RBTreeMap.
+pub struct ExtractIf<'a, K: 'a, V: 'a, F, C = OrdComparator>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    map: &'a mut RBTreeMap<K, V, C>,
+    // the next node to be tested, null at the end
+    next: *mut Node<K, V>,
+    pred: F,
+}
+
+/// A view into a single entry in a map, which may either be vacant or occupied.
+///
+/// This `enum` is constructed from the [`entry`] method on [`RBTreeMap`].
+///
+/// [`entry`]: RBTreeMap::entry
+pub enum Entry<'a, K: 'a, V: 'a, C = OrdComparator> {
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, C>),
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, C>),
+}
+
+/// A view into a vacant entry in a `RBTreeMap`.
+/// It is part of the [`Entry`] enum.
+pub struct VacantEntry<'a, K: 'a, V: 'a, C = OrdComparator> {
+    map: &'a mut RBTreeMap<K, V, C>,
+    key: K,
+    // the node under which the new node is linked, found by `entry`
+    parent: *mut Node<K, V>,
+    dir: Direction,
+}
+
+/// A view into an occupied entry in a `RBTreeMap`.
+/// It is part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, C = OrdComparator> {
+    map: &'a mut RBTreeMap<K, V, C>,
+    node: *mut Node<K, V>,
+}
+
+/// A cursor over a `RBTreeMap`.
+///
+/// A `Cursor` is like an iterator, except that it can freely seek back-and-forth.
+///
+/// Cursors always point to an element in the tree, or to a "ghost" non-element that is
+/// logically located after the last element and before the first one.
+///
+/// Cursors are created with the [`RBTreeMap::lower_bound`] and [`RBTreeMap::upper_bound`]
+/// methods.
+pub struct Cursor<'a, K: 'a, V: 'a, C = OrdComparator> {
+    // null for the ghost non-element
+    current: *mut Node<K, V>,
+    map: &'a RBTreeMap<K, V, C>,
+}
+
+/// A cursor over a `RBTreeMap` with editing operations.
+///
+/// A `CursorMut` is like an iterator, except that it can freely seek back-and-forth, and can
+/// safely mutate the tree during iteration. The nodes around the cursor are reached through
+/// their parent links, so moving, inserting and removing never search the tree from the root.
+///
+/// Cursors are created with the [`RBTreeMap::lower_bound_mut`] and
+/// [`RBTreeMap::upper_bound_mut`] methods.
+pub struct CursorMut<'a, K: 'a, V: 'a, C = OrdComparator> {
+    // null for the ghost non-element
+    current: *mut Node<K, V>,
+    map: &'a mut RBTreeMap<K, V, C>,
 }
 
 impl<K, V> Node<K, V> {
     pub fn new(key: K, value: V, color: Color) -> *mut Node<K, V> {
         Box::into_raw(Box::new(Node {
-            key: key,
-            value: value,
+            key,
+            value,
             left: ptr::null_mut(),
             right: ptr::null_mut(),
             parent: ptr::null_mut(),
-            color: color,
+            color,
+            size: 1,
         }))
-        // use unsafe { Box::from_raw(node); } to destruct a Node
+        // use unsafe { Node::into_entry(node) } to destruct a Node
     }
 
-    unsafe fn free_node(node: *mut Node<K, V>) {
-        if !node.is_null() {
-            Box::from_raw(node);
-        }
+    // Frees a node that has been unlinked from the tree, moving its key and value out.
+    unsafe fn into_entry(node: *mut Node<K, V>) -> (K, V) {
+        let node = Box::from_raw(node);
+        (node.key, node.value)
     }
 
     unsafe fn left_of(node: *mut Node<K, V>) -> *mut Node<K, V> {
@@ -87,30 +239,257 @@ impl<K, V> Node<K, V> {
     unsafe fn is_red(node: *mut Node<K, V>) -> bool {
         !node.is_null() && (*node).color == Color::Red
     }
+
+    // Returns the number of black nodes on a path from node down to a leaf,
+    // including node itself.
+    unsafe fn black_height(node: *mut Node<K, V>) -> usize {
+        let mut h = 0;
+        let mut x = node;
+        while !x.is_null() {
+            if !Node::is_red(x) {
+                h += 1;
+            }
+            x = (*x).left;
+        }
+        h
+    }
+
+    unsafe fn size_of(node: *mut Node<K, V>) -> usize {
+        if !node.is_null() {
+            (*node).size
+        } else {
+            0
+        }
+    }
+
+    // Recomputes the subtree size of node from its children.
+    unsafe fn update_size(node: *mut Node<K, V>) {
+        (*node).size = Node::size_of((*node).left) + Node::size_of((*node).right) + 1;
+    }
+
+    // Returns the leftmost node of the subtree rooted at node.
+    unsafe fn first(node: *mut Node<K, V>) -> *mut Node<K, V> {
+        let mut x = node;
+        while !Node::left_of(x).is_null() {
+            x = (*x).left;
+        }
+        x
+    }
+
+    // Returns the rightmost node of the subtree rooted at node.
+    unsafe fn last(node: *mut Node<K, V>) -> *mut Node<K, V> {
+        let mut x = node;
+        while !Node::right_of(x).is_null() {
+            x = (*x).right;
+        }
+        x
+    }
+
+    // Returns the next node in key order, or null when node is the last one.
+    unsafe fn successor(node: *mut Node<K, V>) -> *mut Node<K, V> {
+        if !(*node).right.is_null() {
+            return Node::first((*node).right);
+        }
+        let mut x = node;
+        let mut p = (*x).parent;
+        while !p.is_null() && x == (*p).right {
+            x = p;
+            p = (*p).parent;
+        }
+        p
+    }
+
+    // Returns the previous node in key order, or null when node is the first one.
+    unsafe fn predecessor(node: *mut Node<K, V>) -> *mut Node<K, V> {
+        if !(*node).left.is_null() {
+            return Node::last((*node).left);
+        }
+        let mut x = node;
+        let mut p = (*x).parent;
+        while !p.is_null() && x == (*p).left {
+            x = p;
+            p = (*p).parent;
+        }
+        p
+    }
 }
 
-impl<'a, K, V> RBTreeMap<K, V> {
-    /// Makes a new, empty `RBTreeMap`.
+impl<K, V> RBTreeMap<K, V> {
+    /// Makes a new, empty `RBTreeMap`, ordering its keys by their `Ord` implementation.
     pub fn new() -> RBTreeMap<K, V> {
-        Self {
+        Self::with_comparator(OrdComparator)
+    }
+
+    /// Builds a map from entries sorted by key, in O(n) time.
+    ///
+    /// Consecutive entries with equal keys are deduplicated, keeping the last
+    /// one, like collecting into a map keeps the last value inserted for a key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key is less than the key before it.
+    pub fn from_sorted_iter<I>(iter: I) -> RBTreeMap<K, V>
+    where
+        K: Ord,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        Self::from_sorted_with(iter, OrdComparator)
+    }
+
+    /// Builds a map from a vector of entries sorted by key, in O(n) time.
+    ///
+    /// Consecutive entries with equal keys are deduplicated, keeping the last
+    /// one. When the keys are strictly ascending, the entries are moved into
+    /// the tree without an intermediate copy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a key is less than the key before it.
+    pub fn from_sorted_vec(entries: Vec<(K, V)>) -> RBTreeMap<K, V>
+    where
+        K: Ord,
+    {
+        let cmp = OrdComparator;
+        if entries
+            .windows(2)
+            .all(|w| cmp.compare(&w[0].0, &w[1].0).is_lt())
+        {
+            Self::build_sorted(entries, cmp)
+        } else {
+            Self::from_sorted_with(entries, cmp)
+        }
+    }
+}
+
+impl<'a, K, V, C> RBTreeMap<K, V, C> {
+    /// Makes a new, empty `RBTreeMap` ordering its keys by `cmp`.
+    pub fn with_comparator(cmp: C) -> RBTreeMap<K, V, C> {
+        RBTreeMap {
             size: 0,
             root: ptr::null_mut(),
+            cmp,
+            augment: None,
+        }
+    }
+
+    // Makes a new, empty map ordering its keys by `cmp` and keeping the data of
+    // its nodes up to date with `augment`.
+    pub(crate) fn with_augment(cmp: C, augment: unsafe fn(*mut Node<K, V>)) -> RBTreeMap<K, V, C> {
+        let mut map = Self::with_comparator(cmp);
+        map.augment = Some(augment);
+        map
+    }
+
+    pub(crate) fn root_node(&self) -> *mut Node<K, V> {
+        self.root
+    }
+
+    // Builds a map from entries sorted by `cmp`, see `from_sorted_iter`.
+    fn from_sorted_with<I>(iter: I, cmp: C) -> RBTreeMap<K, V, C>
+    where
+        C: Comparator<K>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let iter = iter.into_iter();
+        let mut entries: Vec<(K, V)> = Vec::with_capacity(iter.size_hint().0);
+        for (key, value) in iter {
+            if let Some(last) = entries.last_mut() {
+                match cmp.compare(&last.0, &key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => {
+                        *last = (key, value);
+                        continue;
+                    }
+                    Ordering::Greater => panic!("keys must be sorted in ascending order"),
+                }
+            }
+            entries.push((key, value));
+        }
+        Self::build_sorted(entries, cmp)
+    }
+
+    // Builds a perfectly balanced tree from entries with strictly ascending keys.
+    //
+    // Every subtree splits its nodes evenly between the two sides, so all leaves
+    // are on the last two levels. The nodes on the last level are colored red
+    // when that level is incomplete, and all other nodes black, which gives
+    // every path to a leaf the same number of black nodes.
+    fn build_sorted(entries: Vec<(K, V)>, cmp: C) -> RBTreeMap<K, V, C> {
+        let n = entries.len();
+        let deepest = (usize::BITS - 1 - (n + 1).leading_zeros()) as usize;
+        let red_depth = if (n + 1).is_power_of_two() {
+            usize::MAX
+        } else {
+            deepest
+        };
+        let mut iter = entries.into_iter();
+        let root = unsafe { Self::build_subtree(n, 0, red_depth, &mut iter) };
+        RBTreeMap {
+            size: n,
+            root,
+            cmp,
+            augment: None,
+        }
+    }
+
+    // Builds the subtree of the next n entries in order, with the root at depth.
+    unsafe fn build_subtree<I>(
+        n: usize,
+        depth: usize,
+        red_depth: usize,
+        iter: &mut I,
+    ) -> *mut Node<K, V>
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if n == 0 {
+            return ptr::null_mut();
+        }
+        let n_left = (n - 1) / 2;
+        let left = Self::build_subtree(n_left, depth + 1, red_depth, iter);
+        let (key, value) = iter.next().unwrap();
+        let color = if depth == red_depth {
+            Color::Red
+        } else {
+            Color::Black
+        };
+        let node = Node::new(key, value, color);
+        let right = Self::build_subtree(n - 1 - n_left, depth + 1, red_depth, iter);
+        (*node).left = left;
+        (*node).right = right;
+        for child in [left, right] {
+            if !child.is_null() {
+                (*child).parent = node;
+            }
         }
+        (*node).size = n;
+        node
     }
 
+    /// Returns the number of elements in the map.
     pub fn len(&self) -> usize {
-        return self.size;
+        self.size
+    }
+
+    /// Returns `true` if the map contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
     }
 
     /// Clears the map, removing all elements from the map
     pub fn clear(&mut self) {
-        *self = RBTreeMap::new();
+        drop(self.take_entries());
     }
 
     /// Returns the value corresponding to the key
-    pub fn get(&self, key: &K) -> Option<&V>
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
-        K: Ord,
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
     {
         unsafe {
             let node = self.search_node(key);
@@ -122,25 +501,55 @@ impl<'a, K, V> RBTreeMap<K, V> {
         }
     }
 
+    /// Returns a mutable reference to the value corresponding to the key
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        unsafe {
+            let node = self.search_node(key);
+            if !node.is_null() {
+                Some(&mut (*node).value)
+            } else {
+                None
+            }
+        }
+    }
+
     /// Returns the key-value pair corresponding to the key
-    pub fn get_key_value(&self, key: &K) -> Option<(&K, &V)>
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
     where
-        K: Ord,
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
     {
         let node = self.search_node(key);
         unsafe {
             if !node.is_null() {
-                return Some((&(*node).key, &(*node).value));
+                Some((&(*node).key, &(*node).value))
             } else {
-                return None;
+                None
             }
         }
     }
 
     /// Returns `true` if the map contains a value for the specified key.
-    pub fn contains_key(&self, key: &K) -> bool
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
-        K: Ord,
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
     {
         self.get(key).is_some()
     }
@@ -153,7 +562,43 @@ impl<'a, K, V> RBTreeMap<K, V> {
     /// value is returned.
     pub fn insert(&mut self, key: K, value: V) -> Option<V>
     where
-        K: Ord,
+        C: Comparator<K>,
+    {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    // Inserts a key-value pair like `insert`, but when the key is present also
+    // replaces the stored key, returning the old key and value.
+    pub(crate) fn replace_entry(&mut self, key: K, value: V) -> Option<(K, V)>
+    where
+        C: Comparator<K>,
+    {
+        let node = self.search_node(&key);
+        if node.is_null() {
+            self.insert(key, value);
+            return None;
+        }
+        unsafe {
+            let old_key = replace(&mut (*node).key, key);
+            Some((old_key, replace(&mut (*node).value, value)))
+        }
+    }
+
+    // Returns the comparator ordering the keys of the map.
+    pub(crate) fn comparator(&self) -> &C {
+        &self.cmp
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C>
+    where
+        C: Comparator<K>,
     {
         let mut cur = self.root;
         let mut p = ptr::null_mut();
@@ -161,191 +606,190 @@ impl<'a, K, V> RBTreeMap<K, V> {
 
         unsafe {
             while !cur.is_null() {
-                p = cur;
-                match key.cmp(&(*cur).key) {
+                match self.cmp.compare(&key, &(*cur).key) {
                     Ordering::Less => {
+                        p = cur;
                         cur = (*cur).left;
                         dir = Direction::Left;
                     }
                     Ordering::Greater => {
+                        p = cur;
                         cur = (*cur).right;
                         dir = Direction::Right;
                     }
                     Ordering::Equal => {
-                        break;
+                        return Entry::Occupied(OccupiedEntry {
+                            map: self,
+                            node: cur,
+                        });
                     }
                 }
             }
-            if !p.is_null() {
-                if p == cur {
-                    // key already exists
-                    let mut old_value = std::mem::replace(&mut (*cur).value, value);
-
-                    Some(old_value) // old value is returned
-                } else {
-                    // new node inserting
-                    let mut new_node = Node::new(key, value, Color::Red);
-                    match dir {
-                        Direction::Left => (*p).left = new_node,
-                        Direction::Right => (*p).right = new_node,
-                    }
-                    (*new_node).parent = p;
-                    self.fix_after_insertion(new_node);
-                    Node::set_color(self.root, Color::Black);
-                    self.size += 1;
-                    None
-                }
-            } else {
-                // empty tree case, set new root
-                self.root = Node::new(key, value, Color::Black);
-                self.size = 1;
+        }
+        Entry::Vacant(VacantEntry {
+            map: self,
+            key,
+            parent: p,
+            dir,
+        })
+    }
 
-                None
+    // Links a new node as the `dir` child of `p`, which must be a free slot,
+    // and rebalances from there. A null `p` means the tree is empty.
+    unsafe fn insert_node(
+        &mut self,
+        p: *mut Node<K, V>,
+        dir: Direction,
+        key: K,
+        value: V,
+    ) -> *mut Node<K, V> {
+        let new_node;
+        if !p.is_null() {
+            new_node = Node::new(key, value, Color::Red);
+            match dir {
+                Direction::Left => (*p).left = new_node,
+                Direction::Right => (*p).right = new_node,
             }
+            (*new_node).parent = p;
+            let mut x = p;
+            while !x.is_null() {
+                (*x).size += 1;
+                x = (*x).parent;
+            }
+            self.augment_upward(new_node);
+            self.fix_after_insertion(new_node);
+            Node::set_color(self.root, Color::Black);
+        } else {
+            // empty tree case, set new root
+            new_node = Node::new(key, value, Color::Black);
+            self.root = new_node;
+            self.augment_upward(new_node);
         }
+        self.size += 1;
+        new_node
     }
 
-    /// Removes a key from the map, returning the stored key and value if the key
+    /// Removes a key from the map, returning the value at the key if the key
     /// was previously in the map.
-    pub fn remove(&mut self, key: &K) -> Option<V>
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
     where
-        K: Ord + Default,
-        V: Default,
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
     {
         self.remove_entry(key).map(|e| e.1)
     }
 
     /// Removes a key from the map, returning the stored key and value if the key
     /// was previously in the map.
-    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)>
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
     where
-        K: Ord + Default,
-        V: Default,
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
     {
-        unsafe {
-            let mut x = self.search_node(key);
-            if x.is_null() {
-                return None;
-            }
-            self.size -= 1;
-            let result = Some((
-                replace(&mut (*x).key, Default::default()),
-                replace(&mut (*x).value, Default::default()),
-            )); // can we move key and value out of Node without Default::default()?
+        let x = self.search_node(key);
+        if x.is_null() {
+            return None;
+        }
+        unsafe { Some(self.remove_node(x)) }
+    }
 
-            if !(*x).left.is_null() && !(*x).right.is_null() {
-                let mut y = (*x).right;
-                // find y, the successor of z
-                while !(*y).left.is_null() {
-                    y = (*y).left;
-                }
-                // copy data of y to x and then to remove the successor
-                swap(&mut (*x).key, &mut (*y).key);
-                swap(&mut (*x).value, &mut (*y).value);
-                x = y;
-            }
+    // Unlinks a node of this tree, rebalances, and returns its key and value.
+    unsafe fn remove_node(&mut self, x: *mut Node<K, V>) -> (K, V) {
+        self.unlink_node(x);
+        Node::into_entry(x)
+    }
 
-            if !(*x).left.is_null() || !(*x).right.is_null() {
-                // x has only 1 child: replace and return
-                // in this case, replacement must be red
-                let mut replacement = (*x).left;
-                if (*x).left.is_null() {
-                    replacement = (*x).right;
-                }
+    // Unlinks a node of this tree and rebalances. The node is left detached
+    // with null links, to be freed or linked again by the caller.
+    unsafe fn unlink_node(&mut self, x: *mut Node<K, V>) {
+        self.size -= 1;
 
-                let p = (*x).parent;
+        if !(*x).left.is_null() && !(*x).right.is_null() {
+            let mut y = (*x).right;
+            // find y, the successor of x
+            while !(*y).left.is_null() {
+                y = (*y).left;
+            }
+            // move x down to the position of y, which has no left child,
+            // and then remove x from there
+            self.swap_with_successor(x, y);
+        }
 
-                (*replacement).parent = p;
-                if !p.is_null() {
-                    if x == Node::left_of(p) {
-                        (*p).left = replacement;
-                    } else {
-                        (*p).right = replacement;
-                    }
-                } else {
-                    self.root = replacement;
-                }
+        // x is about to be unlinked, take it out of the subtree sizes above it.
+        // A leaf stays in place during fix_after_deletion, so it counts as empty.
+        (*x).size = 0;
+        let mut p = (*x).parent;
+        while !p.is_null() {
+            (*p).size -= 1;
+            p = (*p).parent;
+        }
 
-                Node::set_color(replacement, Color::Black);
-                Node::free_node(x);
-            } else {
-                // x is leaf: remove and fix
-                if !Node::is_red(x) {
-                    self.fix_after_deletion(x);
-                }
+        if !(*x).left.is_null() || !(*x).right.is_null() {
+            // x has only 1 child: replace and return
+            // in this case, replacement must be red
+            let mut replacement = (*x).left;
+            if (*x).left.is_null() {
+                replacement = (*x).right;
+            }
 
-                let p = (*x).parent;
-                if !p.is_null() {
-                    if x == Node::left_of(p) {
-                        (*p).left = ptr::null_mut();
-                    } else {
-                        (*p).right = ptr::null_mut();
-                    }
+            let p = (*x).parent;
+
+            (*replacement).parent = p;
+            if !p.is_null() {
+                if x == Node::left_of(p) {
+                    (*p).left = replacement;
                 } else {
-                    self.root = ptr::null_mut();
+                    (*p).right = replacement;
                 }
-                Node::free_node(x);
+            } else {
+                self.root = replacement;
             }
-            Node::set_color(self.root, Color::Black);
-            result
-        }
-    }
 
-    pub fn iter(&'a self) -> Iter<'a, K, V> {
-        let mut stack = Vec::new();
-        let mut x = self.root;
-        while !x.is_null() {
-            stack.push(x);
-            unsafe {
-                x = (*x).left;
+            Node::set_color(replacement, Color::Black);
+        } else {
+            // x is leaf: remove and fix
+            if !Node::is_red(x) {
+                self.fix_after_deletion(x);
             }
-        }
-
-        Iter {
-            stack,
-            phantom: ptr::null_mut(),
-        }
-    }
 
-    fn search_node(&self, key: &K) -> *mut Node<K, V>
-    where
-        K: Ord,
-    {
-        let mut cur = self.root;
-        unsafe {
-            while !cur.is_null() {
-                match key.cmp(&(*cur).key) {
-                    Ordering::Less => cur = (*cur).left,
-                    Ordering::Greater => cur = (*cur).right,
-                    Ordering::Equal => return cur,
+            let p = (*x).parent;
+            if !p.is_null() {
+                if x == Node::left_of(p) {
+                    (*p).left = ptr::null_mut();
+                } else {
+                    (*p).right = ptr::null_mut();
                 }
+            } else {
+                self.root = ptr::null_mut();
             }
         }
-        ptr::null_mut()
-    }
+        self.augment_upward((*x).parent);
+        Node::set_color(self.root, Color::Black);
 
-    // Assumes node and its right child are not null
-    // Rotation here exchanges their colors.
-    // When node.right is red, it will not violate rules of Red-Black tree.
-    unsafe fn rotate_left(&mut self, node: *mut Node<K, V>) {
-        if node.is_null() {
-            return;
-        }
-        let x = node;
-        let y = Node::right_of(x);
-        assert!(!y.is_null());
+        (*x).left = ptr::null_mut();
+        (*x).right = ptr::null_mut();
+        (*x).parent = ptr::null_mut();
+        (*x).size = 1;
+    }
 
+    // Exchanges the positions, colors and subtree sizes of x and its successor y in the tree,
+    // relinking the nodes rather than their keys and values so that neither
+    // has to be moved out of its node.
+    // Assumes x has two children, so y is the leftmost node of x.right.
+    unsafe fn swap_with_successor(&mut self, x: *mut Node<K, V>, y: *mut Node<K, V>) {
         let p = (*x).parent;
-        let ly = Node::left_of(y);
-
-        (*x).right = ly;
-        if !ly.is_null() {
-            (*ly).parent = x;
-        }
-        (*y).left = x;
-        (*y).parent = (*x).parent;
-        (*x).parent = y;
-        swap(&mut (*x).color, &mut (*y).color);
+        let xl = (*x).left;
+        let xr = (*x).right;
+        let yp = (*y).parent;
+        let yr = (*y).right;
 
         if !p.is_null() {
             if (*p).left == x {
@@ -356,216 +800,2160 @@ impl<'a, K, V> RBTreeMap<K, V> {
         } else {
             self.root = y;
         }
-    }
+        (*y).parent = p;
 
-    // Assumes node and its left child are not null
-    // Rotation here exchanges their colors.
-    // When node.left is red, it will not violate rules of Red-Black tree.
-    unsafe fn rotate_right(&mut self, node: *mut Node<K, V>) {
-        if node.is_null() {
-            return;
-        }
-        let x = node;
-        let y = Node::left_of(x);
-        assert!(!y.is_null());
+        (*y).left = xl;
+        (*xl).parent = y;
 
-        let p = (*x).parent;
-        let ry = Node::right_of(y);
+        if y == xr {
+            //     x            y
+            //    / \          / \
+            //   xl  y   =>   xl  x
+            //        \            \
+            //        yr           yr
+            (*y).right = x;
+            (*x).parent = y;
+        } else {
+            (*y).right = xr;
+            (*xr).parent = y;
+            (*yp).left = x;
+            (*x).parent = yp;
+        }
 
-        (*x).left = ry;
-        if !ry.is_null() {
-            (*ry).parent = x;
+        (*x).left = ptr::null_mut();
+        (*x).right = yr;
+        if !yr.is_null() {
+            (*yr).parent = x;
         }
-        (*y).right = x;
-        (*y).parent = (*x).parent;
-        (*x).parent = y;
         swap(&mut (*x).color, &mut (*y).color);
+        swap(&mut (*x).size, &mut (*y).size);
+    }
 
-        if !p.is_null() {
-            if (*p).left == x {
-                (*p).left = y;
-            } else {
-                (*p).right = y;
+    /// Gets an iterator over the entries of the map, sorted by key.
+    pub fn iter(&'a self) -> Iter<'a, K, V> {
+        Iter {
+            raw: RawIter::new(self.root),
+            length: self.size,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Gets a mutable iterator over the entries of the map, sorted by key.
+    pub fn iter_mut(&'a mut self) -> IterMut<'a, K, V> {
+        IterMut {
+            raw: RawIter::new(self.root),
+            length: self.size,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Gets an iterator over the keys of the map, in sorted order.
+    pub fn keys(&'a self) -> Keys<'a, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// Gets an iterator over the values of the map, in order by key.
+    pub fn values(&'a self) -> Values<'a, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// Gets a mutable iterator over the values of the map, in order by key.
+    pub fn values_mut(&'a mut self) -> ValuesMut<'a, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the keys, in sorted order.
+    /// The map cannot be used after calling this.
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Creates a consuming iterator visiting all the values, in order by key.
+    /// The map cannot be used after calling this.
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Constructs a double-ended iterator over a sub-range of elements in the map.
+    ///
+    /// The simplest way is to use the range syntax `min..max`, thus `range(min..max)` will
+    /// yield elements from min (inclusive) to max (exclusive). `range((Excluded(4), Included(10)))`
+    /// will yield a left-exclusive, right-inclusive range from 4 to 10.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn range<T, R>(&'a self, range: R) -> Range<'a, K, V>
+    where
+        K: Borrow<T>,
+        T: ?Sized,
+        C: Comparator<T>,
+        R: RangeBounds<T>,
+    {
+        Range {
+            raw: self.raw_range(range),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Constructs a mutable double-ended iterator over a sub-range of elements in the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn range_mut<T, R>(&'a mut self, range: R) -> RangeMut<'a, K, V>
+    where
+        K: Borrow<T>,
+        T: ?Sized,
+        C: Comparator<T>,
+        R: RangeBounds<T>,
+    {
+        RangeMut {
+            raw: self.raw_range(range),
+            phantom: PhantomData,
+        }
+    }
+
+    // Finds the first and the last node inside the range.
+    fn raw_range<T, R>(&self, range: R) -> RawIter<K, V>
+    where
+        K: Borrow<T>,
+        T: ?Sized,
+        C: Comparator<T>,
+        R: RangeBounds<T>,
+    {
+        self.check_range(&range);
+
+        let front = self.first_after(|k| match range.start_bound() {
+            Bound::Included(s) => self.cmp.compare(k.borrow(), s).is_lt(),
+            Bound::Excluded(s) => self.cmp.compare(k.borrow(), s).is_le(),
+            Bound::Unbounded => false,
+        });
+        let back = self.last_while(|k| match range.end_bound() {
+            Bound::Included(e) => self.cmp.compare(k.borrow(), e).is_le(),
+            Bound::Excluded(e) => self.cmp.compare(k.borrow(), e).is_lt(),
+            Bound::Unbounded => true,
+        });
+        unsafe {
+            if front.is_null()
+                || back.is_null()
+                || self
+                    .cmp
+                    .compare((*front).key.borrow(), (*back).key.borrow())
+                    .is_gt()
+            {
+                // no key lies inside the range
+                return RawIter {
+                    front: ptr::null_mut(),
+                    back: ptr::null_mut(),
+                };
+            }
+        }
+        RawIter { front, back }
+    }
+
+    // Returns the last node whose key satisfies `pred`, assuming it holds for
+    // a prefix of the keys in order, or null when it holds for none.
+    fn last_while<F>(&self, pred: F) -> *mut Node<K, V>
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut found = ptr::null_mut();
+        let mut x = self.root;
+        unsafe {
+            while !x.is_null() {
+                if pred(&(*x).key) {
+                    found = x;
+                    x = (*x).right;
+                } else {
+                    x = (*x).left;
+                }
+            }
+        }
+        found
+    }
+
+    // Returns the first node whose key does not satisfy `pred`, assuming it
+    // holds for a prefix of the keys in order, or null when it holds for all.
+    fn first_after<F>(&self, pred: F) -> *mut Node<K, V>
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut found = ptr::null_mut();
+        let mut x = self.root;
+        unsafe {
+            while !x.is_null() {
+                if pred(&(*x).key) {
+                    x = (*x).right;
+                } else {
+                    found = x;
+                    x = (*x).left;
+                }
             }
-        } else {
-            self.root = y;
         }
+        found
     }
 
-    unsafe fn fix_after_insertion(&mut self, node: *mut Node<K, V>) {
+    // Borrows the key and value of a node, or returns `None` for null.
+    fn entry_of(&self, node: *mut Node<K, V>) -> Option<(&K, &V)> {
         if node.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*node).key, &(*node).value)) }
+    }
+
+    fn check_range<T, R>(&self, range: &R)
+    where
+        T: ?Sized,
+        C: Comparator<T>,
+        R: RangeBounds<T>,
+    {
+        match (range.start_bound(), range.end_bound()) {
+            (Bound::Excluded(s), Bound::Excluded(e)) if self.cmp.compare(s, e).is_eq() => {
+                panic!("range start and end are equal and excluded in RBTreeMap")
+            }
+            (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e))
+                if self.cmp.compare(s, e).is_gt() =>
+            {
+                panic!("range start is greater than range end in RBTreeMap")
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the first key-value pair in the map.
+    /// The key in this pair is the minimum key in the map.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        unsafe { self.entry_of(Node::first(self.root)) }
+    }
+
+    /// Returns the last key-value pair in the map.
+    /// The key in this pair is the maximum key in the map.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        unsafe { self.entry_of(Node::last(self.root)) }
+    }
+
+    /// Removes and returns the first element in the map.
+    /// The key of this element is the minimum key that was in the map.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        if self.root.is_null() {
+            return None;
+        }
+        unsafe { Some(self.remove_node(Node::first(self.root))) }
+    }
+
+    /// Removes and returns the last element in the map.
+    /// The key of this element is the maximum key that was in the map.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        if self.root.is_null() {
+            return None;
+        }
+        unsafe { Some(self.remove_node(Node::last(self.root))) }
+    }
+
+    /// Returns the key-value pair with the greatest key less than or equal to `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.entry_of(self.last_while(|k| self.cmp.compare(k.borrow(), key).is_le()))
+    }
+
+    /// Returns the key-value pair with the least key greater than or equal to `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.entry_of(self.first_after(|k| self.cmp.compare(k.borrow(), key).is_lt()))
+    }
+
+    /// Returns the key-value pair with the greatest key strictly less than `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn lower<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.entry_of(self.last_while(|k| self.cmp.compare(k.borrow(), key).is_lt()))
+    }
+
+    /// Returns the key-value pair with the least key strictly greater than `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn higher<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.entry_of(self.first_after(|k| self.cmp.compare(k.borrow(), key).is_le()))
+    }
+
+    /// Splits the collection into two at the given key. Returns everything after the given key,
+    /// including the key.
+    ///
+    /// The tree is cut along the search path of `key` and the pieces are joined back
+    /// together, which takes O(log n) time.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q> + Clone,
+    {
+        debug_assert!(self.augment.is_none(), "split_off on an augmented tree");
+        unsafe {
+            // every comparison is made before the tree is taken apart, so a
+            // panicking comparator leaves the map as it was
+            let mut path = Vec::new();
+            let mut x = self.root;
+            while !x.is_null() {
+                let goes_left = self.cmp.compare(key, (*x).key.borrow()).is_le();
+                path.push(goes_left);
+                x = if goes_left { (*x).left } else { (*x).right };
+            }
+            let bh = Node::black_height(self.root);
+            let ((left, _), (right, _)) = self.split(self.root, bh, &path);
+            let right_size = Node::size_of(right);
+            self.root = left;
+            self.size -= right_size;
+            RBTreeMap {
+                size: right_size,
+                root: right,
+                cmp: self.cmp.clone(),
+                augment: None,
+            }
+        }
+    }
+
+    /// Moves all elements from `other` into `self`, leaving `other` empty.
+    ///
+    /// If a key from `other` is already present in `self`, the respective
+    /// value from `self` will be overwritten with the respective value from `other`.
+    ///
+    /// When all keys of one map are less than all keys of the other, the two trees
+    /// are joined in O(log n) time. Otherwise the entries of `other` are inserted
+    /// one by one.
+    pub fn append(&mut self, other: &mut Self)
+    where
+        C: Comparator<K>,
+    {
+        debug_assert!(
+            self.augment.is_none() && other.augment.is_none(),
+            "append on an augmented tree"
+        );
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            swap(self, other);
             return;
         }
-        let mut x = node;
-        Node::set_color(x, Color::Red);
 
-        while !x.is_null() && self.root != x && (*Node::parent_of(x)).color == Color::Red {
-            let p = Node::parent_of(x);
-            let g = Node::parent_of(p);
+        let self_first = self.first_key_value().unwrap().0;
+        let self_last = self.last_key_value().unwrap().0;
+        let other_first = other.first_key_value().unwrap().0;
+        let other_last = other.last_key_value().unwrap().0;
+        unsafe {
+            if self.cmp.compare(self_last, other_first).is_lt() {
+                let pivot = Node::first(other.root);
+                other.unlink_node(pivot);
+                let (right, right_size) = other.take_root();
+                self.size += right_size + 1;
+                self.join_roots(self.root, pivot, right);
+            } else if self.cmp.compare(other_last, self_first).is_lt() {
+                let pivot = Node::last(other.root);
+                other.unlink_node(pivot);
+                let (left, left_size) = other.take_root();
+                self.size += left_size + 1;
+                self.join_roots(left, pivot, self.root);
+            } else {
+                for (key, value) in other.take_entries() {
+                    self.insert(key, value);
+                }
+            }
+        }
+    }
 
-            if p == Node::left_of(g) {
-                //      g
-                //     / \
-                //    p   u
-                //    |
-                //    x
-                // u and g may be null
-                let mut u = Node::right_of(g);
-                if Node::is_red(u) {
-                    Node::set_color(p, Color::Black);
-                    Node::set_color(u, Color::Black);
-                    Node::set_color(g, Color::Red);
-                    x = g;
-                } else {
-                    //      g              g
-                    //     / \            / \
-                    //    p   u          x   u
-                    //     \            /
-                    //      x    ==>   p
-                    if x == Node::right_of(p) {
-                        self.rotate_left(p);
-                        x = p;
-                    }
-                    //      g             p
-                    //     / \           / \
-                    //    p   u         x   g
-                    //   /                   \
-                    //  x       ==>           u
-                    self.rotate_right(g);
+    /// Concatenates `left`, the entry `key`/`value` and `right` into a single map.
+    ///
+    /// The trees are joined at the height where their black heights match,
+    /// which takes O(log n) time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some key of `left` is not less than `key`,
+    /// or some key of `right` is not greater than `key`.
+    ///
+    /// The result orders its keys with the comparator of `left`.
+    pub fn join(mut left: Self, key: K, value: V, mut right: Self) -> Self
+    where
+        C: Comparator<K>,
+    {
+        debug_assert!(
+            left.augment.is_none() && right.augment.is_none(),
+            "join of an augmented tree"
+        );
+        if let Some((k, _)) = left.last_key_value() {
+            assert!(
+                left.cmp.compare(k, &key).is_lt(),
+                "keys of the left map must be less than the pivot"
+            );
+        }
+        if let Some((k, _)) = right.first_key_value() {
+            assert!(
+                left.cmp.compare(k, &key).is_gt(),
+                "keys of the right map must be greater than the pivot"
+            );
+        }
+        let (r, right_size) = right.take_root();
+        left.size += right_size + 1;
+        unsafe { left.join_roots(left.root, Node::new(key, value, Color::Black), r) };
+        left
+    }
+
+    // Makes the join of two detached trees and a detached pivot node the tree
+    // of this map, assuming the keys are ordered. The size is left to the caller.
+    unsafe fn join_roots(
+        &mut self,
+        left: *mut Node<K, V>,
+        pivot: *mut Node<K, V>,
+        right: *mut Node<K, V>,
+    ) {
+        let bl = Node::black_height(left);
+        let br = Node::black_height(right);
+        let (root, _) = self.join_nodes(left, bl, pivot, right, br);
+        self.root = root;
+    }
+
+    // Detaches the tree from the map, returning its root and size and leaving
+    // the map empty.
+    fn take_root(&mut self) -> (*mut Node<K, V>, usize) {
+        (
+            replace(&mut self.root, ptr::null_mut()),
+            take(&mut self.size),
+        )
+    }
+
+    // Moves the entries into an owning iterator, leaving the map empty.
+    fn take_entries(&mut self) -> IntoIter<K, V> {
+        let (root, length) = self.take_root();
+        unsafe {
+            IntoIter {
+                front: Node::first(root),
+                back: Node::last(root),
+                length,
+            }
+        }
+    }
+
+    // Joins two detached trees with black roots and black heights bl and br,
+    // and a detached pivot node whose key lies between them.
+    // Returns the root of the joined tree and its black height.
+    //
+    // The shorter tree is hung, under a red pivot, from the black node of
+    // the same black height on the facing spine of the taller tree, and the
+    // red violation this may cause is fixed as after an insertion. This takes
+    // O(|bl - br| + 1) time.
+    unsafe fn join_nodes(
+        &mut self,
+        left: *mut Node<K, V>,
+        bl: usize,
+        pivot: *mut Node<K, V>,
+        right: *mut Node<K, V>,
+        br: usize,
+    ) -> Subtree<K, V> {
+        if bl == br {
+            (*pivot).left = left;
+            (*pivot).right = right;
+            (*pivot).parent = ptr::null_mut();
+            (*pivot).color = Color::Black;
+            for child in [left, right] {
+                if !child.is_null() {
+                    (*child).parent = pivot;
                 }
-            } else {
-                // Symmetric case
-                let mut u = Node::left_of(g);
-                if Node::is_red(u) {
-                    Node::set_color(p, Color::Black);
-                    Node::set_color(u, Color::Black);
-                    Node::set_color(g, Color::Red);
-                    x = g;
-                } else {
-                    if x == Node::left_of(p) {
-                        self.rotate_right(p);
-                        x = p;
-                    }
-                    self.rotate_left(g);
+            }
+            Node::update_size(pivot);
+            return (pivot, bl + 1);
+        }
+
+        let (taller, h_taller, shorter) = if bl > br {
+            (left, bl, right)
+        } else {
+            (right, br, left)
+        };
+        let h_shorter = bl.min(br);
+
+        // find c, the black node on the facing spine with the same black height
+        // as the shorter tree, c may be null
+        let mut c = taller;
+        let mut p = ptr::null_mut();
+        let mut h = h_taller;
+        while h != h_shorter || Node::is_red(c) {
+            if !Node::is_red(c) {
+                h -= 1;
+            }
+            p = c;
+            c = if bl > br { (*c).right } else { (*c).left };
+        }
+
+        //        p                p
+        //         \                \
+        //          c     =>        pivot
+        //                          /   \
+        //                         c   shorter
+        // (mirrored when the right tree is taller)
+        if bl > br {
+            (*p).right = pivot;
+            (*pivot).left = c;
+            (*pivot).right = shorter;
+        } else {
+            (*p).left = pivot;
+            (*pivot).left = shorter;
+            (*pivot).right = c;
+        }
+        (*pivot).parent = p;
+        for child in [c, shorter] {
+            if !child.is_null() {
+                (*child).parent = pivot;
+            }
+        }
+        Node::update_size(pivot);
+        let added = Node::size_of(shorter) + 1;
+        let mut x = p;
+        while !x.is_null() {
+            (*x).size += added;
+            x = (*x).parent;
+        }
+
+        // fix the taller tree up as if it was the whole tree
+        let root = replace(&mut self.root, taller);
+        let grew = self.fix_after_insertion(pivot);
+        let joined = replace(&mut self.root, root);
+        (joined, h_taller + grew as usize)
+    }
+
+    // Splits a detached tree with a black root and black height bh along a
+    // search path, given as the turns taken from the root, with `true` for
+    // left. Returns the trees of the nodes left of the path and of the nodes
+    // right of it, with their black heights. A node on the path belongs to the
+    // right tree when the path turns left at it.
+    //
+    // Each node on the search path is detached and used as the pivot to join
+    // its subtree on the far side of `key` back to the pieces below it. The
+    // join costs telescope over the path, so this takes O(log n) time.
+    unsafe fn split(
+        &mut self,
+        node: *mut Node<K, V>,
+        bh: usize,
+        path: &[bool],
+    ) -> (Subtree<K, V>, Subtree<K, V>) {
+        let (&goes_left, path) = match path.split_first() {
+            Some(turn) if !node.is_null() => turn,
+            _ => return ((ptr::null_mut(), 0), (ptr::null_mut(), 0)),
+        };
+
+        // detach both children, painting them black to make them proper trees
+        let mut children = [((*node).left, 0), ((*node).right, 0)];
+        for (child, h) in children.iter_mut() {
+            *h = bh - 1;
+            if !child.is_null() {
+                (**child).parent = ptr::null_mut();
+                if Node::is_red(*child) {
+                    (**child).color = Color::Black;
+                    *h += 1;
                 }
             }
         }
-        Node::set_color(self.root, Color::Black);
+        let [(l, bl), (r, br)] = children;
+        (*node).left = ptr::null_mut();
+        (*node).right = ptr::null_mut();
+        (*node).size = 1;
+
+        if goes_left {
+            let (less, (ge, bge)) = self.split(l, bl, path);
+            (less, self.join_nodes(ge, bge, node, r, br))
+        } else {
+            let ((less, bless), ge) = self.split(r, br, path);
+            (self.join_nodes(l, bl, node, less, bless), ge)
+        }
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, remove all pairs `(k, v)` for which `f(&k, &mut v)` returns `false`.
+    /// The elements are visited in ascending key order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Creates an iterator that visits all elements in ascending key order and
+    /// uses a closure to determine if an element should be removed.
+    ///
+    /// If the closure returns `true`, the element is removed from the map and
+    /// yielded. If the closure returns `false`, or panics, the element remains
+    /// in the map and will not be yielded.
+    ///
+    /// Each element is removed with a complete rebalancing step before it is
+    /// yielded, so the tree stays valid if the closure panics or the iterator
+    /// is dropped before it is exhausted; the remaining elements are then kept.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, C>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let next = unsafe { Node::first(self.root) };
+        ExtractIf {
+            map: self,
+            next,
+            pred,
+        }
+    }
+
+    /// Returns a [`Cursor`] pointing at the first element that is above the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the first
+    /// element of the map.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Cursor {
+            current: self.lower_bound_node(bound),
+            map: self,
+        }
+    }
+
+    /// Returns a [`CursorMut`] pointing at the first element that is above the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the first
+    /// element of the map.
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        CursorMut {
+            current: self.lower_bound_node(bound),
+            map: self,
+        }
+    }
+
+    /// Returns a [`Cursor`] pointing at the last element that is below the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the last
+    /// element of the map.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        Cursor {
+            current: self.upper_bound_node(bound),
+            map: self,
+        }
+    }
+
+    /// Returns a [`CursorMut`] pointing at the last element that is below the
+    /// given bound.
+    ///
+    /// If no such element exists then a cursor pointing at the "ghost"
+    /// non-element is returned.
+    ///
+    /// Passing [`Bound::Unbounded`] will return a cursor pointing at the last
+    /// element of the map.
+    pub fn upper_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        CursorMut {
+            current: self.upper_bound_node(bound),
+            map: self,
+        }
+    }
+
+    fn lower_bound_node<Q>(&self, bound: Bound<&Q>) -> *mut Node<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        match bound {
+            Bound::Included(key) => self.first_after(|k| self.cmp.compare(k.borrow(), key).is_lt()),
+            Bound::Excluded(key) => self.first_after(|k| self.cmp.compare(k.borrow(), key).is_le()),
+            Bound::Unbounded => unsafe { Node::first(self.root) },
+        }
+    }
+
+    fn upper_bound_node<Q>(&self, bound: Bound<&Q>) -> *mut Node<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        match bound {
+            Bound::Included(key) => self.last_while(|k| self.cmp.compare(k.borrow(), key).is_le()),
+            Bound::Excluded(key) => self.last_while(|k| self.cmp.compare(k.borrow(), key).is_lt()),
+            Bound::Unbounded => unsafe { Node::last(self.root) },
+        }
+    }
+
+    /// Returns the key-value pair with the `k`-th smallest key (counting from zero),
+    /// or `None` if `k >= self.len()`.
+    pub fn select(&self, k: usize) -> Option<(&K, &V)> {
+        let mut k = k;
+        let mut x = self.root;
+        unsafe {
+            while !x.is_null() {
+                let ls = Node::size_of((*x).left);
+                match k.cmp(&ls) {
+                    Ordering::Less => x = (*x).left,
+                    Ordering::Equal => return Some((&(*x).key, &(*x).value)),
+                    Ordering::Greater => {
+                        k -= ls + 1;
+                        x = (*x).right;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of keys in the map that are less than `key`.
+    ///
+    /// The key may be any borrowed form of the map's key type, but the ordering
+    /// on the borrowed form *must* match the ordering on the key type.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.count_while(|k| self.cmp.compare(k.borrow(), key).is_lt())
+    }
+
+    /// Returns the number of elements in a sub-range of the map, without visiting them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn count_in_range<T, R>(&self, range: R) -> usize
+    where
+        K: Borrow<T>,
+        T: ?Sized,
+        C: Comparator<T>,
+        R: RangeBounds<T>,
+    {
+        self.check_range(&range);
+
+        let before_start = match range.start_bound() {
+            Bound::Included(s) => self.count_while(|k| self.cmp.compare(k.borrow(), s).is_lt()),
+            Bound::Excluded(s) => self.count_while(|k| self.cmp.compare(k.borrow(), s).is_le()),
+            Bound::Unbounded => 0,
+        };
+        let until_end = match range.end_bound() {
+            Bound::Included(e) => self.count_while(|k| self.cmp.compare(k.borrow(), e).is_le()),
+            Bound::Excluded(e) => self.count_while(|k| self.cmp.compare(k.borrow(), e).is_lt()),
+            Bound::Unbounded => self.size,
+        };
+        until_end.saturating_sub(before_start)
+    }
+
+    // Counts the keys for which `pred` holds, assuming it holds for a prefix
+    // of the keys in order, in a single descent using the subtree sizes.
+    fn count_while<F>(&self, pred: F) -> usize
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut count = 0;
+        let mut x = self.root;
+        unsafe {
+            while !x.is_null() {
+                if pred(&(*x).key) {
+                    count += Node::size_of((*x).left) + 1;
+                    x = (*x).right;
+                } else {
+                    x = (*x).left;
+                }
+            }
+        }
+        count
+    }
+
+    fn search_node<Q>(&self, key: &Q) -> *mut Node<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cur = self.root;
+        unsafe {
+            while !cur.is_null() {
+                match self.cmp.compare(key, (*cur).key.borrow()) {
+                    Ordering::Less => cur = (*cur).left,
+                    Ordering::Greater => cur = (*cur).right,
+                    Ordering::Equal => return cur,
+                }
+            }
+        }
+        ptr::null_mut()
+    }
+
+    // Recomputes the augmented data of node and of all its ancestors.
+    unsafe fn augment_upward(&self, node: *mut Node<K, V>) {
+        if let Some(augment) = self.augment {
+            let mut x = node;
+            while !x.is_null() {
+                augment(x);
+                x = (*x).parent;
+            }
+        }
+    }
+
+    // Assumes node and its right child are not null
+    // Rotation here exchanges their colors.
+    // When node.right is red, it will not violate rules of Red-Black tree.
+    unsafe fn rotate_left(&mut self, node: *mut Node<K, V>) {
+        if node.is_null() {
+            return;
+        }
+        let x = node;
+        let y = Node::right_of(x);
+        assert!(!y.is_null());
+
+        let p = (*x).parent;
+        let ly = Node::left_of(y);
+
+        (*x).right = ly;
+        if !ly.is_null() {
+            (*ly).parent = x;
+        }
+        (*y).left = x;
+        (*y).parent = (*x).parent;
+        (*x).parent = y;
+        swap(&mut (*x).color, &mut (*y).color);
+        (*y).size = (*x).size;
+        Node::update_size(x);
+        if let Some(augment) = self.augment {
+            augment(x);
+            augment(y);
+        }
+
+        if !p.is_null() {
+            if (*p).left == x {
+                (*p).left = y;
+            } else {
+                (*p).right = y;
+            }
+        } else {
+            self.root = y;
+        }
+    }
+
+    // Assumes node and its left child are not null
+    // Rotation here exchanges their colors.
+    // When node.left is red, it will not violate rules of Red-Black tree.
+    unsafe fn rotate_right(&mut self, node: *mut Node<K, V>) {
+        if node.is_null() {
+            return;
+        }
+        let x = node;
+        let y = Node::left_of(x);
+        assert!(!y.is_null());
+
+        let p = (*x).parent;
+        let ry = Node::right_of(y);
+
+        (*x).left = ry;
+        if !ry.is_null() {
+            (*ry).parent = x;
+        }
+        (*y).right = x;
+        (*y).parent = (*x).parent;
+        (*x).parent = y;
+        swap(&mut (*x).color, &mut (*y).color);
+        (*y).size = (*x).size;
+        Node::update_size(x);
+        if let Some(augment) = self.augment {
+            augment(x);
+            augment(y);
+        }
+
+        if !p.is_null() {
+            if (*p).left == x {
+                (*p).left = y;
+            } else {
+                (*p).right = y;
+            }
+        } else {
+            self.root = y;
+        }
+    }
+
+    // Returns `true` when the color flips reach the root,
+    // which means the black height of the tree has grown by one.
+    unsafe fn fix_after_insertion(&mut self, node: *mut Node<K, V>) -> bool {
+        if node.is_null() {
+            return false;
+        }
+        let mut x = node;
+        Node::set_color(x, Color::Red);
+
+        while !x.is_null() && self.root != x && (*Node::parent_of(x)).color == Color::Red {
+            let p = Node::parent_of(x);
+            let g = Node::parent_of(p);
+
+            if p == Node::left_of(g) {
+                //      g
+                //     / \
+                //    p   u
+                //    |
+                //    x
+                // u and g may be null
+                let u = Node::right_of(g);
+                if Node::is_red(u) {
+                    Node::set_color(p, Color::Black);
+                    Node::set_color(u, Color::Black);
+                    Node::set_color(g, Color::Red);
+                    x = g;
+                } else {
+                    //      g              g
+                    //     / \            / \
+                    //    p   u          x   u
+                    //     \            /
+                    //      x    ==>   p
+                    if x == Node::right_of(p) {
+                        self.rotate_left(p);
+                        x = p;
+                    }
+                    //      g             p
+                    //     / \           / \
+                    //    p   u         x   g
+                    //   /                   \
+                    //  x       ==>           u
+                    self.rotate_right(g);
+                }
+            } else {
+                // Symmetric case
+                let u = Node::left_of(g);
+                if Node::is_red(u) {
+                    Node::set_color(p, Color::Black);
+                    Node::set_color(u, Color::Black);
+                    Node::set_color(g, Color::Red);
+                    x = g;
+                } else {
+                    if x == Node::left_of(p) {
+                        self.rotate_right(p);
+                        x = p;
+                    }
+                    self.rotate_left(g);
+                }
+            }
+        }
+        let grew = Node::is_red(self.root);
+        Node::set_color(self.root, Color::Black);
+        grew
+    }
+
+    unsafe fn fix_after_deletion(&mut self, node: *mut Node<K, V>) {
+        let mut x = node;
+
+        while x != self.root && !Node::is_red(x) {
+            let p = Node::parent_of(x);
+            if x == Node::left_of(p) {
+                let mut y = Node::right_of(p);
+
+                if Node::is_red(y) {
+                    self.rotate_left(p);
+                    y = Node::right_of(p);
+                }
+
+                if !Node::is_red(Node::left_of(y)) && !Node::is_red(Node::right_of(y)) {
+                    // no red links to take, subtract 1 black depth from sibling,
+                    // try to increase black depth of parent node in next iteration
+                    Node::set_color(y, Color::Red);
+                    x = p;
+                } else {
+                    // at least one of sibling's children is red, take this link
+                    if Node::is_red(Node::left_of(y)) {
+                        self.rotate_right(y);
+                        y = Node::parent_of(y);
+                    }
+
+                    //          p              y
+                    //         / \            / \
+                    //        x   y          p  ry
+                    //           / \        / \
+                    //          ly ry  =>  x  ly
+                    // dist   1  2  1      2  2  1
+                    // dist: number of black links to root of this subtree
+                    self.rotate_left(p);
+                    Node::set_color(Node::right_of(y), Color::Black);
+                    x = self.root;
+                }
+            } else {
+                // symmetric case: x is right child of p
+                let mut y = Node::left_of(p);
+                if Node::is_red(y) {
+                    self.rotate_right(p);
+                    y = Node::left_of(p);
+                }
+
+                if !Node::is_red(Node::left_of(y)) && !Node::is_red(Node::right_of(y)) {
+                    // no red links to take, subtract 1 black depth from sibling,
+                    // try to increase black depth of parent node in next iteration
+                    Node::set_color(y, Color::Red);
+                    x = p;
+                } else {
+                    // at least one of sibling's children is red, take this link
+                    if Node::is_red(Node::right_of(y)) {
+                        self.rotate_left(y);
+                        y = Node::parent_of(y);
+                    }
+                    self.rotate_right(p);
+                    Node::set_color(Node::left_of(y), Color::Black);
+                    x = self.root;
+                }
+            }
+        }
+
+        Node::set_color(x, Color::Black);
+    }
+}
+
+// Invariant checks for the tests, on the default comparator so that the
+// tests can call them without naming it.
+#[cfg(test)]
+impl<K, V> RBTreeMap<K, V> {
+    /// check if the subtree sizes and parent links under node are consistent,
+    /// returns the size of the tree rooted at node
+    pub(crate) fn check_links(node: *mut Node<K, V>) -> Option<usize> {
+        if node.is_null() {
+            return Some(0);
+        }
+        unsafe {
+            for child in [(*node).left, (*node).right] {
+                if !child.is_null() && (*child).parent != node {
+                    return None;
+                }
+            }
+            let size = Self::check_links((*node).left)? + Self::check_links((*node).right)? + 1;
+            if size != (*node).size {
+                return None;
+            }
+            Some(size)
+        }
+    }
+
+    /// check if a tree rooted at node is a 2-3-4 tree,
+    /// when it's 2-3-4 tree, returns the black height of root,
+    /// when it's not, returns `None`
+    pub(crate) fn is_234tree(node: *mut Node<K, V>) -> Option<usize> {
+        if node.is_null() {
+            return Some(0);
+        }
+        unsafe {
+            let bh_left = Self::is_234tree((*node).left)?;
+            let bh_right = Self::is_234tree((*node).right)?;
+            if bh_left != bh_right {
+                return None;
+            }
+            if Node::is_red(node) {
+                Some(bh_left)
+            } else {
+                Some(bh_left + 1)
+            }
+        }
+    }
+}
+
+impl<K, V> RawIter<K, V> {
+    fn new(root: *mut Node<K, V>) -> RawIter<K, V> {
+        unsafe {
+            RawIter {
+                front: Node::first(root),
+                back: Node::last(root),
+            }
+        }
+    }
+
+    fn next_node(&mut self) -> *mut Node<K, V> {
+        let x = self.front;
+        if x.is_null() {
+            return x;
+        }
+        if x == self.back {
+            self.front = ptr::null_mut();
+            self.back = ptr::null_mut();
+        } else {
+            self.front = unsafe { Node::successor(x) };
+        }
+        x
+    }
+
+    fn next_back_node(&mut self) -> *mut Node<K, V> {
+        let x = self.back;
+        if x.is_null() {
+            return x;
+        }
+        if x == self.front {
+            self.front = ptr::null_mut();
+            self.back = ptr::null_mut();
+        } else {
+            self.back = unsafe { Node::predecessor(x) };
+        }
+        x
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        let node = self.raw.next_node();
+        unsafe { Some((&(*node).key, &(*node).value)) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        let node = self.raw.next_back_node();
+        unsafe { Some((&(*node).key, &(*node).value)) }
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        let node = self.raw.next_node();
+        unsafe { Some((&(*node).key, &mut (*node).value)) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.length == 0 {
+            return None;
+        }
+        self.length -= 1;
+        let node = self.raw.next_back_node();
+        unsafe { Some((&(*node).key, &mut (*node).value)) }
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a K> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a V> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<&'a mut V> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for ValuesMut<'a, K, V> {}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        if self.length == 0 {
+            return None;
+        }
+        unsafe {
+            // x is the leftmost node, so it is the root or the left child of p.
+            // Splice its right subtree into its place and free it.
+            let x = self.front;
+            let p = (*x).parent;
+            let r = (*x).right;
+            if !r.is_null() {
+                (*r).parent = p;
+                if !p.is_null() {
+                    (*p).left = r;
+                }
+                self.front = Node::first(r);
+            } else {
+                if !p.is_null() {
+                    (*p).left = ptr::null_mut();
+                }
+                self.front = p;
+            }
+            self.length -= 1;
+            Some(Node::into_entry(x))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+
+    fn last(mut self) -> Option<(K, V)> {
+        self.next_back()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        if self.length == 0 {
+            return None;
+        }
+        unsafe {
+            // symmetric case: x is the rightmost node
+            let x = self.back;
+            let p = (*x).parent;
+            let l = (*x).left;
+            if !l.is_null() {
+                (*l).parent = p;
+                if !p.is_null() {
+                    (*p).right = l;
+                }
+                self.back = Node::last(l);
+            } else {
+                if !p.is_null() {
+                    (*p).right = ptr::null_mut();
+                }
+                self.back = p;
+            }
+            self.length -= 1;
+            Some(Node::into_entry(x))
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V> Drop for IntoIter<K, V> {
+    fn drop(&mut self) {
+        // Frees the remaining nodes without recursion.
+        for _ in self.by_ref() {}
+    }
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<K> {
+        self.next_back()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoKeys<K, V> {
+    fn next_back(&mut self) -> Option<K> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+impl<K, V> FusedIterator for IntoKeys<K, V> {}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn last(mut self) -> Option<V> {
+        self.next_back()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoValues<K, V> {
+    fn next_back(&mut self) -> Option<V> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+impl<K, V> FusedIterator for IntoValues<K, V> {}
+
+impl<K, V, C> IntoIterator for RBTreeMap<K, V, C> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Gets an owning iterator over the entries of the map, sorted by key.
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        self.take_entries()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a RBTreeMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a mut RBTreeMap<K, V, C> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.raw.next_node();
+        if node.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*node).key, &(*node).value)) }
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.raw.next_back_node();
+        if node.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*node).key, &(*node).value)) }
+    }
+}
+
+impl<'a, K, V> FusedIterator for Range<'a, K, V> {}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.raw.next_node();
+        if node.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*node).key, &mut (*node).value)) }
+    }
+
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.raw.next_back_node();
+        if node.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*node).key, &mut (*node).value)) }
+    }
+}
+
+impl<'a, K, V> FusedIterator for RangeMut<'a, K, V> {}
+
+impl<'a, K, V, C> Entry<'a, K, V, C> {
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of the default function.
+    /// The function is given a reference to the key that was moved during the `entry` call.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K, V, C> VacantEntry<'a, K, V, C> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the VacantEntry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key,
+    /// and returns a mutable reference to it.
+    ///
+    /// The new node is linked under the parent found by [`RBTreeMap::entry`],
+    /// so no second search is made.
+    pub fn insert(self, value: V) -> &'a mut V {
+        unsafe {
+            let node = self.map.insert_node(self.parent, self.dir, self.key, value);
+            &mut (*node).value
+        }
+    }
+}
+
+impl<'a, K, V, C> OccupiedEntry<'a, K, V, C> {
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        unsafe { &(*self.node).key }
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        unsafe { &(*self.node).value }
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    ///
+    /// If you need a reference to the `OccupiedEntry` that may outlive the
+    /// destruction of the `Entry` value, see [`into_mut`].
+    ///
+    /// [`into_mut`]: OccupiedEntry::into_mut
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut (*self.node).value }
+    }
+
+    /// Converts the entry into a mutable reference to its value.
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut (*self.node).value }
+    }
+
+    /// Sets the value of the entry with the `OccupiedEntry`'s key,
+    /// and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value of the entry out of the map, and returns it.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Take ownership of the key and value from the map.
+    pub fn remove_entry(self) -> (K, V) {
+        unsafe { self.map.remove_node(self.node) }
+    }
+}
+
+impl<'a, K, V, F, C> Iterator for ExtractIf<'a, K, V, F, C>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        unsafe {
+            while !self.next.is_null() {
+                let x = self.next;
+                // removal relinks nodes without moving them, so the successor
+                // stays valid whether or not x is removed
+                self.next = Node::successor(x);
+                if (self.pred)(&(*x).key, &mut (*x).value) {
+                    return Some(self.map.remove_node(x));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len()))
+    }
+}
+
+impl<'a, K, V, F, C> FusedIterator for ExtractIf<'a, K, V, F, C> where F: FnMut(&K, &mut V) -> bool {}
+
+// Returns the node after node in key order, where null is the ghost
+// non-element between the last node and the first one.
+unsafe fn next_of<K, V, C>(map: &RBTreeMap<K, V, C>, node: *mut Node<K, V>) -> *mut Node<K, V> {
+    if node.is_null() {
+        Node::first(map.root)
+    } else {
+        Node::successor(node)
+    }
+}
+
+// Returns the node before node in key order, see `next_of`.
+unsafe fn prev_of<K, V, C>(map: &RBTreeMap<K, V, C>, node: *mut Node<K, V>) -> *mut Node<K, V> {
+    if node.is_null() {
+        Node::last(map.root)
+    } else {
+        Node::predecessor(node)
+    }
+}
+
+impl<'a, K, V, C> Clone for Cursor<'a, K, V, C> {
+    fn clone(&self) -> Self {
+        Cursor {
+            current: self.current,
+            map: self.map,
+        }
+    }
+}
+
+impl<'a, K, V, C> Cursor<'a, K, V, C> {
+    /// Moves the cursor to the next element of the `RBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the first element of the `RBTreeMap`. If it is pointing to the last
+    /// element of the `RBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { next_of(self.map, self.current) };
+    }
+
+    /// Moves the cursor to the previous element of the `RBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the last element of the `RBTreeMap`. If it is pointing to the first
+    /// element of the `RBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { prev_of(self.map, self.current) };
+    }
+
+    /// Returns a reference to the key of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key(&self) -> Option<&'a K> {
+        self.key_value().map(|(k, _)| k)
+    }
+
+    /// Returns a reference to the value of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn value(&self) -> Option<&'a V> {
+        self.key_value().map(|(_, v)| v)
+    }
+
+    /// Returns a reference to the key and value of the element that the cursor
+    /// is currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key_value(&self) -> Option<(&'a K, &'a V)> {
+        self.map.entry_of(self.current)
+    }
+
+    /// Returns a reference to the next element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the first element of the `RBTreeMap`. If it is pointing to the last
+    /// element of the `RBTreeMap` then this returns `None`.
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        self.map
+            .entry_of(unsafe { next_of(self.map, self.current) })
+    }
+
+    /// Returns a reference to the previous element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the last element of the `RBTreeMap`. If it is pointing to the first
+    /// element of the `RBTreeMap` then this returns `None`.
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        self.map
+            .entry_of(unsafe { prev_of(self.map, self.current) })
+    }
+}
+
+impl<'a, K, V, C> CursorMut<'a, K, V, C> {
+    /// Moves the cursor to the next element of the `RBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the first element of the `RBTreeMap`. If it is pointing to the last
+    /// element of the `RBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { next_of(self.map, self.current) };
+    }
+
+    /// Moves the cursor to the previous element of the `RBTreeMap`.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this will move it to
+    /// the last element of the `RBTreeMap`. If it is pointing to the first
+    /// element of the `RBTreeMap` then this will move it to the "ghost" non-element.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { prev_of(self.map, self.current) };
+    }
+
+    /// Returns a reference to the key of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key(&self) -> Option<&K> {
+        self.map.entry_of(self.current).map(|(k, _)| k)
+    }
+
+    /// Returns a reference to the value of the element that the cursor is
+    /// currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn value(&self) -> Option<&V> {
+        self.map.entry_of(self.current).map(|(_, v)| v)
+    }
+
+    /// Returns a reference to the key and value of the element that the cursor
+    /// is currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn key_value(&self) -> Option<(&K, &V)> {
+        self.map.entry_of(self.current)
+    }
+
+    /// Returns a mutable reference to the value of the element that the cursor
+    /// is currently pointing to.
+    ///
+    /// This returns `None` if the cursor is currently pointing to the
+    /// "ghost" non-element.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        if self.current.is_null() {
+            return None;
+        }
+        unsafe { Some(&mut (*self.current).value) }
+    }
+
+    /// Returns a reference to the key and a mutable reference to the value of
+    /// the next element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the first element of the `RBTreeMap`. If it is pointing to the last
+    /// element of the `RBTreeMap` then this returns `None`.
+    pub fn peek_next(&mut self) -> Option<(&K, &mut V)> {
+        unsafe {
+            let next = next_of(self.map, self.current);
+            if next.is_null() {
+                return None;
+            }
+            Some((&(*next).key, &mut (*next).value))
+        }
+    }
+
+    /// Returns a reference to the key and a mutable reference to the value of
+    /// the previous element.
+    ///
+    /// If the cursor is pointing to the "ghost" non-element then this returns
+    /// the last element of the `RBTreeMap`. If it is pointing to the first
+    /// element of the `RBTreeMap` then this returns `None`.
+    pub fn peek_prev(&mut self) -> Option<(&K, &mut V)> {
+        unsafe {
+            let prev = prev_of(self.map, self.current);
+            if prev.is_null() {
+                return None;
+            }
+            Some((&(*prev).key, &mut (*prev).value))
+        }
+    }
+
+    /// Returns a read-only cursor pointing to the current element.
+    ///
+    /// The lifetime of the returned `Cursor` is bound to that of the
+    /// `CursorMut`, which means it cannot outlive the `CursorMut` and that the
+    /// `CursorMut` is frozen for the lifetime of the `Cursor`.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, C> {
+        Cursor {
+            current: self.current,
+            map: self.map,
+        }
+    }
+
+    /// Inserts a new element into the `RBTreeMap` after the current one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new element is
+    /// inserted at the front of the `RBTreeMap`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if:
+    /// - the given key compares less than or equal to the current element (if
+    ///   any).
+    /// - the given key compares greater than or equal to the next element (if
+    ///   any).
+    pub fn insert_after(&mut self, key: K, value: V)
+    where
+        C: Comparator<K>,
+    {
+        unsafe {
+            let next = next_of(self.map, self.current);
+            if !self.current.is_null() {
+                assert!(
+                    self.map.cmp.compare(&key, &(*self.current).key).is_gt(),
+                    "key must be ordered above the current element"
+                );
+            }
+            if !next.is_null() {
+                assert!(
+                    self.map.cmp.compare(&key, &(*next).key).is_lt(),
+                    "key must be ordered below the next element"
+                );
+            }
+
+            // the new node becomes the left child of the next node, when that
+            // slot is free, or the right child of the current node otherwise
+            if !next.is_null() && (*next).left.is_null() {
+                self.map.insert_node(next, Direction::Left, key, value);
+            } else {
+                self.map
+                    .insert_node(self.current, Direction::Right, key, value);
+            }
+        }
+    }
+
+    /// Inserts a new element into the `RBTreeMap` before the current one.
+    ///
+    /// If the cursor is pointing at the "ghost" non-element then the new element is
+    /// inserted at the end of the `RBTreeMap`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if:
+    /// - the given key compares greater than or equal to the current element
+    ///   (if any).
+    /// - the given key compares less than or equal to the previous element (if
+    ///   any).
+    pub fn insert_before(&mut self, key: K, value: V)
+    where
+        C: Comparator<K>,
+    {
+        unsafe {
+            let prev = prev_of(self.map, self.current);
+            if !self.current.is_null() {
+                assert!(
+                    self.map.cmp.compare(&key, &(*self.current).key).is_lt(),
+                    "key must be ordered below the current element"
+                );
+            }
+            if !prev.is_null() {
+                assert!(
+                    self.map.cmp.compare(&key, &(*prev).key).is_gt(),
+                    "key must be ordered above the previous element"
+                );
+            }
+
+            // symmetric case: right child of the previous node, or left child
+            // of the current node
+            if !prev.is_null() && (*prev).right.is_null() {
+                self.map.insert_node(prev, Direction::Right, key, value);
+            } else {
+                self.map
+                    .insert_node(self.current, Direction::Left, key, value);
+            }
+        }
     }
 
-    unsafe fn fix_after_deletion(&mut self, node: *mut Node<K, V>) {
-        let mut x = node;
+    /// Removes the current element from the `RBTreeMap`.
+    ///
+    /// The element that was removed is returned, and the cursor is
+    /// moved to point to the next element in the `RBTreeMap`.
+    ///
+    /// If the cursor is currently pointing to the "ghost" non-element then no element
+    /// is removed and `None` is returned. The cursor is not moved in this case.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.current.is_null() {
+            return None;
+        }
+        unsafe {
+            let current = self.current;
+            self.current = Node::successor(current);
+            Some(self.map.remove_node(current))
+        }
+    }
 
-        while x != self.root && !Node::is_red(x) {
-            let p = Node::parent_of(x);
-            if x == Node::left_of(p) {
-                let mut y = Node::right_of(p);
+    /// Removes the current element from the `RBTreeMap`.
+    ///
+    /// The element that was removed is returned, and the cursor is
+    /// moved to point to the previous element in the `RBTreeMap`.
+    ///
+    /// If the cursor is currently pointing to the "ghost" non-element then no element
+    /// is removed and `None` is returned. The cursor is not moved in this case.
+    pub fn remove_current_and_move_back(&mut self) -> Option<(K, V)> {
+        if self.current.is_null() {
+            return None;
+        }
+        unsafe {
+            let current = self.current;
+            self.current = Node::predecessor(current);
+            Some(self.map.remove_node(current))
+        }
+    }
+}
 
-                if Node::is_red(y) {
-                    self.rotate_left(p);
-                    y = Node::right_of(p);
-                }
+impl<K, V, C: Default> Default for RBTreeMap<K, V, C> {
+    /// Creates an empty `RBTreeMap`, with the default comparator.
+    fn default() -> RBTreeMap<K, V, C> {
+        RBTreeMap::with_comparator(C::default())
+    }
+}
 
-                if !Node::is_red(Node::left_of(y)) && !Node::is_red(Node::right_of(y)) {
-                    // no red links to take, subtract 1 black depth from sibling,
-                    // try to increase black depth of parent node in next iteration
-                    Node::set_color(y, Color::Red);
-                    x = p;
+impl<K: Clone, V: Clone, C: Clone> Clone for RBTreeMap<K, V, C> {
+    /// Copies the tree node by node, keeping its shape and colors.
+    fn clone(&self) -> Self {
+        // Uses a stack of (source node, parent of the copy, side) to
+        // prevent recursion. Copies are linked as soon as they are made,
+        // so a panicking clone leaves a tree that can be dropped.
+        debug_assert!(self.augment.is_none(), "clone of an augmented tree");
+        let mut map = RBTreeMap::with_comparator(self.cmp.clone());
+        unsafe {
+            let mut stack = Vec::new();
+            if !self.root.is_null() {
+                stack.push((self.root, ptr::null_mut(), Direction::Left));
+            }
+            while let Some((src, p, dir)) = stack.pop() {
+                let node = Node::new((*src).key.clone(), (*src).value.clone(), (*src).color);
+                (*node).size = (*src).size;
+                (*node).parent = p;
+                if p.is_null() {
+                    map.root = node;
                 } else {
-                    // at least one of sibling's children is red, take this link
-                    if Node::is_red(Node::left_of(y)) {
-                        self.rotate_right(y);
-                        y = Node::parent_of(y);
+                    match dir {
+                        Direction::Left => (*p).left = node,
+                        Direction::Right => (*p).right = node,
                     }
-
-                    //          p              y
-                    //         / \            / \
-                    //        x   y          p  ry
-                    //           / \        / \
-                    //          ly ry  =>  x  ly
-                    // dist   1  2  1      2  2  1
-                    // dist: number of black links to root of this subtree
-                    self.rotate_left(p);
-                    Node::set_color(Node::right_of(y), Color::Black);
-                    x = self.root;
                 }
-            } else {
-                // symmetric case: x is right child of p
-                let mut y = Node::left_of(p);
-                if Node::is_red(y) {
-                    self.rotate_right(p);
-                    y = Node::left_of(p);
+                if !(*src).left.is_null() {
+                    stack.push(((*src).left, node, Direction::Left));
                 }
-
-                if !Node::is_red(Node::left_of(y)) && !Node::is_red(Node::right_of(y)) {
-                    // no red links to take, subtract 1 black depth from sibling,
-                    // try to increase black depth of parent node in next iteration
-                    Node::set_color(y, Color::Red);
-                    x = p;
-                } else {
-                    // at least one of sibling's children is red, take this link
-                    if Node::is_red(Node::right_of(y)) {
-                        self.rotate_left(y);
-                        y = Node::parent_of(y);
-                    }
-                    self.rotate_right(p);
-                    Node::set_color(Node::left_of(y), Color::Black);
-                    x = self.root;
+                if !(*src).right.is_null() {
+                    stack.push(((*src).right, node, Direction::Right));
                 }
             }
         }
+        map.size = self.size;
+        map
+    }
+}
 
-        Node::set_color(x, Color::Black);
+impl<K: Debug, V: Debug, C> Debug for RBTreeMap<K, V, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
+}
 
-    /// check if a tree rooted at node is a 2-3-4 tree,
-    /// when it's 2-3-4 tree, returns the black height of root,
-    /// when it's not, returns `None`
-    fn is_234tree(node: *mut Node<K, V>) -> Option<usize> {
-        if node.is_null() {
-            return Some(0);
-        }
-        unsafe {
-            let bh_left = Self::is_234tree((*node).left);
-            let bh_right = Self::is_234tree((*node).right);
-            if bh_left.is_none() || bh_right.is_none() {
-                return None;
-            } else if bh_left.unwrap() != bh_right.unwrap() {
-                return None;
-            }
-            if Node::is_red(node) {
-                return bh_left;
-            } else {
-                return Some(bh_left.unwrap() + 1);
-            }
+impl<K: PartialEq, V: PartialEq, C> PartialEq for RBTreeMap<K, V, C> {
+    fn eq(&self, other: &RBTreeMap<K, V, C>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C> Eq for RBTreeMap<K, V, C> {}
+
+impl<K: PartialOrd, V: PartialOrd, C> PartialOrd for RBTreeMap<K, V, C> {
+    fn partial_cmp(&self, other: &RBTreeMap<K, V, C>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord, C> Ord for RBTreeMap<K, V, C> {
+    fn cmp(&self, other: &RBTreeMap<K, V, C>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash, C> Hash for RBTreeMap<K, V, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for entry in self {
+            entry.hash(state);
         }
     }
 }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V> {
-    type Item = (&'a K, &'a V);
+impl<K, Q, V, C> Index<&Q> for RBTreeMap<K, V, C>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    C: Comparator<Q>,
+{
+    type Output = V;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        unsafe {
-            let node = self.stack.pop();
-            if node.is_none() {
-                return None;
-            }
-            let mut x = node.unwrap();
-            if !(*x).right.is_null() {
-                x = (*x).right;
-                self.stack.push(x);
-                while !(*x).left.is_null() {
-                    x = (*x).left;
-                    self.stack.push(x);
-                }
-            }
-            node.map(|node| (&(*node).key, &(*node).value))
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `RBTreeMap`.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, C: Comparator<K> + Default> FromIterator<(K, V)> for RBTreeMap<K, V, C> {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> RBTreeMap<K, V, C> {
+        let mut map = RBTreeMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, C: Comparator<K>> Extend<(K, V)> for RBTreeMap<K, V, C> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
         }
     }
 }
 
-impl<K, V> Drop for RBTreeMap<K, V> {
+impl<'a, K: Copy, V: Copy, C: Comparator<K>> Extend<(&'a K, &'a V)> for RBTreeMap<K, V, C> {
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
+
+// The raw node pointers opt the map and its borrowing types out of Send and Sync.
+// The map owns its nodes like a Box would, and the other types stand for shared
+// or mutable borrows of the map or its entries, so they are given the bounds of
+// what they stand for.
+unsafe impl<K: Send, V: Send, C: Send> Send for RBTreeMap<K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for RBTreeMap<K, V, C> {}
+
+unsafe impl<K: Sync, V: Sync> Send for Iter<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Iter<'_, K, V> {}
+
+unsafe impl<K: Sync, V: Send> Send for IterMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for IterMut<'_, K, V> {}
+
+unsafe impl<K: Sync, V: Sync> Send for Range<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Range<'_, K, V> {}
+
+unsafe impl<K: Sync, V: Send> Send for RangeMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for RangeMut<'_, K, V> {}
+
+unsafe impl<K: Send, V: Send> Send for IntoIter<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for IntoIter<K, V> {}
+
+unsafe impl<K: Sync, V: Sync, C: Sync> Send for Cursor<'_, K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for Cursor<'_, K, V, C> {}
+
+unsafe impl<K: Send, V: Send, C: Send> Send for CursorMut<'_, K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for CursorMut<'_, K, V, C> {}
+
+unsafe impl<K: Send, V: Send, C: Send> Send for VacantEntry<'_, K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for VacantEntry<'_, K, V, C> {}
+
+unsafe impl<K: Send, V: Send, C: Send> Send for OccupiedEntry<'_, K, V, C> {}
+unsafe impl<K: Sync, V: Sync, C: Sync> Sync for OccupiedEntry<'_, K, V, C> {}
+
+unsafe impl<K: Send, V: Send, F: Send, C: Send> Send for ExtractIf<'_, K, V, F, C> where
+    F: FnMut(&K, &mut V) -> bool
+{
+}
+unsafe impl<K: Sync, V: Sync, F: Sync, C: Sync> Sync for ExtractIf<'_, K, V, F, C> where
+    F: FnMut(&K, &mut V) -> bool
+{
+}
+
+impl<K, V, C> Drop for RBTreeMap<K, V, C> {
     fn drop(&mut self) {
         // Uses a stack to record pointers of nodes to be freed.
         // Prevent invoking Node::drop() recursively.
@@ -581,19 +2969,301 @@ impl<K, V> Drop for RBTreeMap<K, V> {
                 if !(*ptr).right.is_null() {
                     stack.push((*ptr).right);
                 }
-                Box::from_raw(ptr);
+                drop(Box::from_raw(ptr));
             }
         }
     }
 }
 
-impl<K, V> Drop for Node<K, V> {
-    fn drop(&mut self) {}
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::RBTreeMap;
+    use crate::comparator::Comparator;
+    use serde::de::{Deserialize, Deserializer, Error, MapAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+    use std::cmp::Ordering;
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    impl<K: Serialize, V: Serialize, C> Serialize for RBTreeMap<K, V, C> {
+        /// Serializes the map as a map, with its entries sorted by key.
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self)
+        }
+    }
+
+    impl<'de, K, V, C> Deserialize<'de> for RBTreeMap<K, V, C>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+        C: Comparator<K> + Default,
+    {
+        /// Deserializes a map, which is built in O(n) time when its keys are
+        /// sorted. Fails if a key appears twice.
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_map(MapVisitor(PhantomData))
+        }
+    }
+
+    struct MapVisitor<K, V, C>(PhantomData<RBTreeMap<K, V, C>>);
+
+    impl<'de, K, V, C> Visitor<'de> for MapVisitor<K, V, C>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+        C: Comparator<K> + Default,
+    {
+        type Value = RBTreeMap<K, V, C>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a map with unique keys")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let cmp = C::default();
+            // the size hint comes from the input, so it is not trusted with
+            // a large allocation
+            let mut entries: Vec<(K, V)> =
+                Vec::with_capacity(access.size_hint().unwrap_or(0).min(4096));
+            let mut sorted = true;
+            while let Some((key, value)) = access.next_entry()? {
+                if let Some((last, _)) = entries.last() {
+                    match cmp.compare(last, &key) {
+                        Ordering::Less => {}
+                        Ordering::Equal => return Err(duplicate_key(entries.len())),
+                        Ordering::Greater => sorted = false,
+                    }
+                }
+                entries.push((key, value));
+            }
+            if sorted {
+                return Ok(RBTreeMap::build_sorted(entries, cmp));
+            }
+
+            let mut map = RBTreeMap::with_comparator(cmp);
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if map.insert(key, value).is_some() {
+                    return Err(duplicate_key(index));
+                }
+            }
+            Ok(map)
+        }
+    }
+
+    fn duplicate_key<E: Error>(index: usize) -> E {
+        E::custom(format_args!("duplicate key at entry {} of the map", index))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::RBTreeMap;
+    use super::{Node, RBTreeMap};
+    use crate::comparator::OrdComparator;
+    use std::ops::Bound;
+
+    #[quickcheck]
+    fn links_and_sizes(v: Vec<i32>) -> bool {
+        let mut map = RBTreeMap::new();
+
+        for &x in v.iter() {
+            map.insert(x, x);
+            if RBTreeMap::check_links(map.root) != Some(map.len()) {
+                return false;
+            }
+        }
+        for &x in v.iter() {
+            map.remove(&x);
+            if RBTreeMap::check_links(map.root) != Some(map.len()) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn is_valid<K, V, C>(map: &RBTreeMap<K, V, C>) -> bool {
+        unsafe {
+            !Node::is_red(map.root)
+                && Node::parent_of(map.root).is_null()
+                && RBTreeMap::<K, V>::is_234tree(map.root) == Some(Node::black_height(map.root))
+                && RBTreeMap::<K, V>::check_links(map.root) == Some(map.len())
+        }
+    }
+
+    #[quickcheck]
+    fn split_off_and_append(v: Vec<i32>, w: Vec<i32>, at: i32) -> bool {
+        let mut map = RBTreeMap::new();
+        for &x in v.iter() {
+            map.insert(x, x);
+        }
+        let mut right = map.split_off(&at);
+        if !is_valid(&map) || !is_valid(&right) {
+            return false;
+        }
+
+        // disjoint key ranges are joined, overlapping ones are merged
+        let mut other = RBTreeMap::new();
+        for &x in w.iter() {
+            other.insert(x, x);
+        }
+        map.append(&mut right);
+        map.append(&mut other);
+        is_valid(&map) && right.is_empty() && other.is_empty()
+    }
+
+    #[quickcheck]
+    fn join(left: Vec<u16>, right: Vec<u16>) -> bool {
+        let mut l = RBTreeMap::new();
+        let mut r = RBTreeMap::new();
+        for &x in left.iter() {
+            l.insert(x as i32, ());
+        }
+        for &x in right.iter() {
+            r.insert(x as i32 + 100_000, ());
+        }
+        let map = RBTreeMap::join(l, 99_999, (), r);
+        is_valid(&map)
+    }
+
+    #[quickcheck]
+    fn cursor_edits(ops: Vec<(u8, bool)>) -> bool {
+        let mut map = RBTreeMap::new();
+        for x in (0..200).step_by(2) {
+            map.insert(x, ());
+        }
+        let mut cursor = map.lower_bound_mut(Bound::Unbounded);
+        for &(op, forward) in ops.iter() {
+            match op % 4 {
+                0 if forward => cursor.move_next(),
+                0 => cursor.move_prev(),
+                1 => {
+                    cursor.remove_current();
+                }
+                _ => {
+                    // insert the odd key next to an even one, if it's missing
+                    if let Some(&k) = cursor.key() {
+                        let free = if forward {
+                            cursor.peek_next().is_none_or(|(&n, _)| n > k + 1)
+                        } else {
+                            cursor.peek_prev().is_none_or(|(&p, _)| p < k - 1)
+                        };
+                        if free && forward {
+                            cursor.insert_after(k + 1, ());
+                        } else if free {
+                            cursor.insert_before(k - 1, ());
+                        }
+                    }
+                }
+            }
+        }
+        is_valid(&map)
+    }
+
+    #[quickcheck]
+    fn extract_if_panic(v: Vec<i32>, panic_at: usize) -> bool {
+        let mut map = RBTreeMap::new();
+        for &x in v.iter() {
+            map.insert(x, x);
+        }
+        let keys: Vec<i32> = map.keys().copied().collect();
+        let panic_at = panic_at % (keys.len() + 2);
+
+        let mut visited = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.retain(|&k, _| {
+                visited += 1;
+                if visited == panic_at {
+                    panic!("retain predicate panicked");
+                }
+                k % 3 != 0
+            })
+        }));
+
+        // keys from the one that panicked onwards are all kept
+        let panicked = panic_at != 0 && panic_at <= keys.len();
+        let expected = keys
+            .iter()
+            .enumerate()
+            .filter(|&(i, &k)| (panicked && i + 1 >= panic_at) || k % 3 != 0)
+            .map(|(_, k)| k);
+        is_valid(&map) && result.is_err() == panicked && map.keys().eq(expected)
+    }
+
+    #[quickcheck]
+    fn split_off_panic(v: Vec<i32>, at: i32, panic_at: u8) -> bool {
+        // the comparator panics once it has been called `left` more times
+        let left = std::cell::Cell::new(usize::MAX);
+        let cmp = |a: &i32, b: &i32| {
+            if left.get() == 0 {
+                panic!("comparator panicked");
+            }
+            left.set(left.get() - 1);
+            a.cmp(b)
+        };
+        let mut map = RBTreeMap::with_comparator(cmp);
+        for &x in v.iter() {
+            map.insert(x, x);
+        }
+        let keys: Vec<i32> = map.keys().copied().collect();
+
+        left.set(usize::from(panic_at));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| map.split_off(&at)));
+        left.set(usize::MAX);
+        match result {
+            // the map is left as it was
+            Err(_) => is_valid(&map) && map.keys().eq(keys.iter()),
+            Ok(right) => {
+                is_valid(&map)
+                    && is_valid(&right)
+                    && map.keys().chain(right.keys()).eq(keys.iter())
+                    && right.keys().all(|&k| k >= at)
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn clone_keeps_shape(v: Vec<i32>) -> bool {
+        let map: RBTreeMap<i32, i32> = v.iter().map(|&x| (x, x)).collect();
+        let copy = map.clone();
+
+        // compare the pre-order sequences of keys, colors and sizes
+        fn shape(map: &RBTreeMap<i32, i32>) -> Vec<(i32, bool, usize)> {
+            let mut out = Vec::new();
+            let mut stack = vec![map.root];
+            while let Some(x) = stack.pop() {
+                if !x.is_null() {
+                    unsafe {
+                        out.push(((*x).key, Node::is_red(x), (*x).size));
+                        stack.push((*x).right);
+                        stack.push((*x).left);
+                    }
+                }
+            }
+            out
+        }
+        is_valid(&copy) && shape(&map) == shape(&copy)
+    }
+
+    #[test]
+    fn from_sorted_sizes() {
+        for n in 0..300 {
+            let map = RBTreeMap::from_sorted_iter((0..n).map(|x| (x, x)));
+            assert!(is_valid(&map), "invalid tree of {} entries", n);
+            assert!(map.keys().copied().eq(0..n));
+        }
+    }
+
+    #[quickcheck]
+    fn from_sorted(v: Vec<i32>) -> bool {
+        let mut v = v;
+        v.sort();
+        let map = RBTreeMap::from_sorted_vec(v.iter().map(|&x| (x, x)).collect());
+        let mut map2 = RBTreeMap::from_sorted_iter(v.iter().map(|&x| (x, ())));
+        for &x in v.iter().step_by(2) {
+            map2.remove(&x);
+        }
+        is_valid(&map) && is_valid(&map2)
+    }
 
     #[quickcheck]
     fn is_234tree(v: Vec<i32>) -> bool {
@@ -615,6 +3285,18 @@ mod tests {
         true
     }
 
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "augmented tree")]
+    fn split_off_augmented() {
+        unsafe fn touch(_: *mut Node<i32, i32>) {}
+
+        let mut map = RBTreeMap::with_augment(OrdComparator, touch);
+        map.insert(1, 1);
+        map.insert(2, 2);
+        map.split_off(&2);
+    }
+
     // #[test]
     // fn reproduce () {
     //     let mut map = RBTreeMap::new();
@@ -0,0 +1,594 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::iter::{FusedIterator, Peekable};
+use std::ops::RangeBounds;
+
+use crate::comparator::{Comparator, OrdComparator};
+use crate::rbtree::{self, RBTreeMap};
+
+/// An ordered set based on a red-black tree, storing its elements as the keys
+/// of a [`RBTreeMap`] with `()` values.
+///
+/// Elements are ordered by the comparator `C`, which defaults to their `Ord`
+/// implementation. The set operations [`union`], [`intersection`], [`difference`]
+/// and [`symmetric_difference`] are lazy iterators merging the in-order
+/// traversals of both sets, ordered by the comparator of `self`.
+///
+/// [`union`]: RBTreeSet::union
+/// [`intersection`]: RBTreeSet::intersection
+/// [`difference`]: RBTreeSet::difference
+/// [`symmetric_difference`]: RBTreeSet::symmetric_difference
+pub struct RBTreeSet<T, C = OrdComparator> {
+    map: RBTreeMap<T, (), C>,
+}
+
+/// An iterator over the elements of a `RBTreeSet`, in ascending order.
+///
+/// This `struct` is created by the [`iter`] method on [`RBTreeSet`].
+///
+/// [`iter`]: RBTreeSet::iter
+pub struct Iter<'a, T: 'a> {
+    iter: rbtree::Keys<'a, T, ()>,
+}
+
+/// An owning iterator over the elements of a `RBTreeSet`, in ascending order.
+///
+/// This `struct` is created by the `into_iter` method on [`RBTreeSet`].
+pub struct IntoIter<T> {
+    iter: rbtree::IntoKeys<T, ()>,
+}
+
+/// An iterator over a sub-range of elements in a `RBTreeSet`.
+///
+/// This `struct` is created by the [`range`] method on [`RBTreeSet`].
+///
+/// [`range`]: RBTreeSet::range
+pub struct Range<'a, T: 'a> {
+    iter: rbtree::Range<'a, T, ()>,
+}
+
+/// A lazy iterator producing elements in the union of `RBTreeSet`s.
+///
+/// This `struct` is created by the [`union`] method on [`RBTreeSet`].
+///
+/// [`union`]: RBTreeSet::union
+pub struct Union<'a, T: 'a, C = OrdComparator> {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+    cmp: &'a C,
+}
+
+/// A lazy iterator producing elements in the intersection of `RBTreeSet`s.
+///
+/// This `struct` is created by the [`intersection`] method on [`RBTreeSet`].
+///
+/// [`intersection`]: RBTreeSet::intersection
+pub struct Intersection<'a, T: 'a, C = OrdComparator> {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+    cmp: &'a C,
+}
+
+/// A lazy iterator producing elements in the difference of `RBTreeSet`s.
+///
+/// This `struct` is created by the [`difference`] method on [`RBTreeSet`].
+///
+/// [`difference`]: RBTreeSet::difference
+pub struct Difference<'a, T: 'a, C = OrdComparator> {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+    cmp: &'a C,
+}
+
+/// A lazy iterator producing elements in the symmetric difference of `RBTreeSet`s.
+///
+/// This `struct` is created by the [`symmetric_difference`] method on [`RBTreeSet`].
+///
+/// [`symmetric_difference`]: RBTreeSet::symmetric_difference
+pub struct SymmetricDifference<'a, T: 'a, C = OrdComparator> {
+    a: Peekable<Iter<'a, T>>,
+    b: Peekable<Iter<'a, T>>,
+    cmp: &'a C,
+}
+
+impl<T> RBTreeSet<T> {
+    /// Makes a new, empty `RBTreeSet`, ordering its elements by their `Ord` implementation.
+    pub fn new() -> RBTreeSet<T> {
+        RBTreeSet {
+            map: RBTreeMap::new(),
+        }
+    }
+}
+
+impl<T, C> RBTreeSet<T, C> {
+    /// Makes a new, empty `RBTreeSet` ordering its elements by `cmp`.
+    pub fn with_comparator(cmp: C) -> RBTreeSet<T, C> {
+        RBTreeSet {
+            map: RBTreeMap::with_comparator(cmp),
+        }
+    }
+
+    /// Returns the number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the set contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Clears the set, removing all elements.
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    /// Gets an iterator that visits the elements in the set in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            iter: self.map.keys(),
+        }
+    }
+
+    /// Returns a reference to the first element in the set, if any.
+    pub fn first(&self) -> Option<&T> {
+        self.map.first_key_value().map(|(k, _)| k)
+    }
+
+    /// Returns a reference to the last element in the set, if any.
+    pub fn last(&self) -> Option<&T> {
+        self.map.last_key_value().map(|(k, _)| k)
+    }
+
+    /// Removes the first element from the set and returns it, if any.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.map.pop_first().map(|(k, _)| k)
+    }
+
+    /// Removes the last element from the set and returns it, if any.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.map.pop_last().map(|(k, _)| k)
+    }
+
+    /// Adds a value to the set.
+    ///
+    /// Returns whether the value was newly inserted. If the set already
+    /// contained an equal value, the set is not modified and the stored value
+    /// is kept.
+    pub fn insert(&mut self, value: T) -> bool
+    where
+        C: Comparator<T>,
+    {
+        match self.map.entry(value) {
+            rbtree::Entry::Vacant(entry) => {
+                entry.insert(());
+                true
+            }
+            rbtree::Entry::Occupied(_) => false,
+        }
+    }
+
+    /// Adds a value to the set, replacing the existing equal value, if any,
+    /// and returning it.
+    pub fn replace(&mut self, value: T) -> Option<T>
+    where
+        C: Comparator<T>,
+    {
+        self.map.replace_entry(value, ()).map(|(k, _)| k)
+    }
+
+    /// Returns `true` if the set contains an element equal to the value.
+    ///
+    /// The value may be any borrowed form of the set's element type, but the
+    /// ordering on the borrowed form *must* match the ordering on the element type.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Returns a reference to the element in the set, if any, that is equal to the value.
+    ///
+    /// The value may be any borrowed form of the set's element type, but the
+    /// ordering on the borrowed form *must* match the ordering on the element type.
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.get_key_value(value).map(|(k, _)| k)
+    }
+
+    /// Removes a value from the set. Returns whether such an element was present.
+    ///
+    /// The value may be any borrowed form of the set's element type, but the
+    /// ordering on the borrowed form *must* match the ordering on the element type.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Removes and returns the element in the set, if any, that is equal to the value.
+    ///
+    /// The value may be any borrowed form of the set's element type, but the
+    /// ordering on the borrowed form *must* match the ordering on the element type.
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.map.remove_entry(value).map(|(k, _)| k)
+    }
+
+    /// Constructs a double-ended iterator over a sub-range of elements in the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if range `start > end`.
+    /// Panics if range `start == end` and both bounds are `Excluded`.
+    pub fn range<K, R>(&self, range: R) -> Range<'_, T>
+    where
+        T: Borrow<K>,
+        K: ?Sized,
+        C: Comparator<K>,
+        R: RangeBounds<K>,
+    {
+        Range {
+            iter: self.map.range(range),
+        }
+    }
+
+    /// Visits the elements representing the union, i.e., all the elements in
+    /// `self` or `other`, without duplicates, in ascending order.
+    ///
+    /// Both sets must be ordered the same way, and equal elements are taken from `self`.
+    pub fn union<'a>(&'a self, other: &'a RBTreeSet<T, C>) -> Union<'a, T, C> {
+        Union {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+            cmp: self.map.comparator(),
+        }
+    }
+
+    /// Visits the elements representing the intersection, i.e., the elements
+    /// that are both in `self` and `other`, in ascending order.
+    ///
+    /// Both sets must be ordered the same way, and elements are taken from `self`.
+    pub fn intersection<'a>(&'a self, other: &'a RBTreeSet<T, C>) -> Intersection<'a, T, C> {
+        Intersection {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+            cmp: self.map.comparator(),
+        }
+    }
+
+    /// Visits the elements representing the difference, i.e., the elements
+    /// that are in `self` but not in `other`, in ascending order.
+    ///
+    /// Both sets must be ordered the same way.
+    pub fn difference<'a>(&'a self, other: &'a RBTreeSet<T, C>) -> Difference<'a, T, C> {
+        Difference {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+            cmp: self.map.comparator(),
+        }
+    }
+
+    /// Visits the elements representing the symmetric difference, i.e., the
+    /// elements that are in `self` or in `other` but not in both, in ascending order.
+    ///
+    /// Both sets must be ordered the same way.
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a RBTreeSet<T, C>,
+    ) -> SymmetricDifference<'a, T, C> {
+        SymmetricDifference {
+            a: self.iter().peekable(),
+            b: other.iter().peekable(),
+            cmp: self.map.comparator(),
+        }
+    }
+
+    /// Returns `true` if the set is a subset of another,
+    /// i.e., `other` contains at least all the elements in `self`.
+    pub fn is_subset(&self, other: &RBTreeSet<T, C>) -> bool
+    where
+        C: Comparator<T>,
+    {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+
+    /// Returns `true` if the set is a superset of another,
+    /// i.e., `self` contains at least all the elements in `other`.
+    pub fn is_superset(&self, other: &RBTreeSet<T, C>) -> bool
+    where
+        C: Comparator<T>,
+    {
+        other.is_subset(self)
+    }
+
+    /// Returns `true` if `self` has no elements in common with `other`.
+    pub fn is_disjoint(&self, other: &RBTreeSet<T, C>) -> bool
+    where
+        C: Comparator<T>,
+    {
+        self.intersection(other).next().is_none()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(k, _)| k)
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.iter.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<'a, T> FusedIterator for Range<'a, T> {}
+
+impl<'a, T, C: Comparator<T>> Iterator for Union<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let ord = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => self.cmp.compare(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        match ord {
+            Ordering::Less => self.a.next(),
+            Ordering::Greater => self.b.next(),
+            Ordering::Equal => {
+                self.b.next();
+                self.a.next()
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let a = self.a.len();
+        let b = self.b.len();
+        (a.max(b), Some(a + b))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> FusedIterator for Union<'a, T, C> {}
+
+impl<'a, T, C: Comparator<T>> Iterator for Intersection<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let a = *self.a.peek()?;
+            let b = *self.b.peek()?;
+            match self.cmp.compare(a, b) {
+                Ordering::Less => {
+                    self.a.next();
+                }
+                Ordering::Greater => {
+                    self.b.next();
+                }
+                Ordering::Equal => {
+                    self.b.next();
+                    return self.a.next();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.a.len().min(self.b.len())))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> FusedIterator for Intersection<'a, T, C> {}
+
+impl<'a, T, C: Comparator<T>> Iterator for Difference<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let a = *self.a.peek()?;
+            let Some(&b) = self.b.peek() else {
+                return self.a.next();
+            };
+            match self.cmp.compare(a, b) {
+                Ordering::Less => return self.a.next(),
+                Ordering::Greater => {
+                    self.b.next();
+                }
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let a = self.a.len();
+        (a.saturating_sub(self.b.len()), Some(a))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> FusedIterator for Difference<'a, T, C> {}
+
+impl<'a, T, C: Comparator<T>> Iterator for SymmetricDifference<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(a), Some(b)) => self.cmp.compare(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+            match ord {
+                Ordering::Less => return self.a.next(),
+                Ordering::Greater => return self.b.next(),
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.a.len() + self.b.len()))
+    }
+}
+
+impl<'a, T, C: Comparator<T>> FusedIterator for SymmetricDifference<'a, T, C> {}
+
+impl<T, C> IntoIterator for RBTreeSet<T, C> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter {
+            iter: self.map.into_keys(),
+        }
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a RBTreeSet<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T, C: Default> Default for RBTreeSet<T, C> {
+    /// Creates an empty `RBTreeSet`, with the default comparator.
+    fn default() -> RBTreeSet<T, C> {
+        RBTreeSet {
+            map: RBTreeMap::default(),
+        }
+    }
+}
+
+impl<T: Clone, C: Clone> Clone for RBTreeSet<T, C> {
+    fn clone(&self) -> Self {
+        RBTreeSet {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: Debug, C> Debug for RBTreeSet<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, C> PartialEq for RBTreeSet<T, C> {
+    fn eq(&self, other: &RBTreeSet<T, C>) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Eq, C> Eq for RBTreeSet<T, C> {}
+
+impl<T: PartialOrd, C> PartialOrd for RBTreeSet<T, C> {
+    fn partial_cmp(&self, other: &RBTreeSet<T, C>) -> Option<Ordering> {
+        self.map.partial_cmp(&other.map)
+    }
+}
+
+impl<T: Ord, C> Ord for RBTreeSet<T, C> {
+    fn cmp(&self, other: &RBTreeSet<T, C>) -> Ordering {
+        self.map.cmp(&other.map)
+    }
+}
+
+impl<T: Hash, C> Hash for RBTreeSet<T, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.map.hash(state)
+    }
+}
+
+impl<T, C: Comparator<T> + Default> FromIterator<T> for RBTreeSet<T, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> RBTreeSet<T, C> {
+        let mut set = RBTreeSet::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for RBTreeSet<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a, C: Comparator<T>> Extend<&'a T> for RBTreeSet<T, C> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
@@ -0,0 +1,102 @@
+use dsgym_rs::avltree_map::AVLTreeMap;
+use dsgym_rs::rbtree::RBTreeMap;
+use std::collections::BTreeMap;
+
+extern crate quickcheck;
+#[macro_use(quickcheck)]
+extern crate quickcheck_macros;
+
+#[test]
+fn basics() {
+    let mut map = AVLTreeMap::new();
+    assert_eq!(map.insert("A", 1), None);
+    assert_eq!(map.insert("B", 2), None);
+    assert_eq!(map.insert("C", 3), None);
+    assert_eq!(map.insert("B", 20), Some(2));
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get(&"B"), Some(&20));
+    assert_eq!(map.get(&"ZZZ"), None);
+    assert_eq!(map.get_key_value(&"A"), Some((&"A", &1)));
+    assert!(map.contains_key(&"C"));
+
+    *map.get_mut(&"C").unwrap() += 1;
+    assert_eq!(map[&"C"], 4);
+
+    assert_eq!(map.remove(&"B"), Some(20));
+    assert_eq!(map.remove(&"B"), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(format!("{:?}", map), r#"{"A": 1, "C": 4}"#);
+    map.clear();
+    assert!(map.is_empty());
+}
+
+#[test]
+fn iter() {
+    let map: AVLTreeMap<i32, i32> = (0..100).rev().map(|x| (x, x * x)).collect();
+    assert!(map.iter().map(|(k, _)| *k).eq(0..100));
+    assert!(map.keys().rev().copied().eq((0..100).rev()));
+    assert_eq!(map.values().nth(3), Some(&9));
+    assert_eq!(map.first_key_value(), Some((&0, &0)));
+    assert_eq!(map.last_key_value(), Some((&99, &9801)));
+
+    // both ends stop where they meet
+    let mut it = map.iter();
+    for x in 0..50 {
+        assert_eq!(it.next().map(|(k, _)| *k), Some(x));
+        assert_eq!(it.next_back().map(|(k, _)| *k), Some(99 - x));
+    }
+    assert_eq!(it.len(), 0);
+    assert_eq!(it.next(), None);
+    assert_eq!(it.next_back(), None);
+
+    let mut into = map.clone().into_iter();
+    assert_eq!(into.next(), Some((0, 0)));
+    assert_eq!(into.len(), 99);
+    assert!(map.into_iter().map(|(k, _)| k).eq(0..100));
+}
+
+#[test]
+fn comparator() {
+    let mut map = AVLTreeMap::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+    for x in 0..10 {
+        map.insert(x, ());
+    }
+    assert!(map.keys().copied().eq((0..10).rev()));
+    assert_eq!(map.pop_first(), Some((9, ())));
+    assert_eq!(map.pop_last(), Some((0, ())));
+}
+
+#[quickcheck]
+fn same_as_rbtreemap(ops: Vec<(u8, i8, i32)>) -> bool {
+    let mut avl = AVLTreeMap::new();
+    let mut rb = RBTreeMap::new();
+    for (op, k, v) in ops {
+        let same = match op % 4 {
+            0 | 1 => avl.insert(k, v) == rb.insert(k, v),
+            2 => avl.remove_entry(&k) == rb.remove_entry(&k),
+            _ => {
+                avl.get_mut(&k).map(|x| *x = x.wrapping_add(v))
+                    == rb.get_mut(&k).map(|x| *x = x.wrapping_add(v))
+            }
+        };
+        if !same || avl.len() != rb.len() {
+            return false;
+        }
+    }
+    avl.iter().eq(rb.iter()) && avl.iter().rev().eq(rb.iter().rev())
+}
+
+#[quickcheck]
+fn like_btreemap(v: Vec<(i8, i32)>, removed: Vec<i8>) -> bool {
+    let mut map: AVLTreeMap<i8, i32> = v.iter().copied().collect();
+    let mut expected: BTreeMap<i8, i32> = v.into_iter().collect();
+    for k in removed {
+        if map.remove(&k) != expected.remove(&k) {
+            return false;
+        }
+    }
+    map.len() == expected.len()
+        && map.iter().eq(expected.iter())
+        && expected.keys().all(|k| map.get(k) == expected.get(k))
+        && map.into_iter().eq(expected)
+}
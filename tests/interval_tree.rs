@@ -0,0 +1,132 @@
+use dsgym_rs::interval_tree::IntervalTreeMap;
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+extern crate quickcheck;
+#[macro_use(quickcheck)]
+extern crate quickcheck_macros;
+
+#[test]
+fn basics() {
+    let mut map = IntervalTreeMap::new();
+    assert_eq!(map.insert(1..=5, "a"), None);
+    assert_eq!(map.insert(3..=3, "b"), None);
+    assert_eq!(map.insert(1..=2, "c"), None);
+    assert_eq!(map.insert(1..=5, "d"), Some("a"));
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get(&(1..=5)), Some(&"d"));
+    assert_eq!(map.get(&(1..=4)), None);
+    assert!(map.contains_key(&(3..=3)));
+
+    *map.get_mut(&(3..=3)).unwrap() = "e";
+    let entries: Vec<_> = map.iter().map(|(r, v)| (r.clone(), *v)).collect();
+    assert_eq!(entries, vec![(1..=2, "c"), (1..=5, "d"), (3..=3, "e")]);
+
+    assert_eq!(map.remove(&(1..=5)), Some("d"));
+    assert_eq!(map.remove(&(1..=5)), None);
+    assert_eq!(map.len(), 2);
+    map.clear();
+    assert!(map.is_empty());
+}
+
+#[test]
+fn overlapping_and_stabbing() {
+    let map: IntervalTreeMap<i32, char> =
+        vec![(0..=2, 'a'), (2..=4, 'b'), (5..=9, 'c'), (6..=6, 'd')]
+            .into_iter()
+            .collect();
+
+    let values = |it: &mut dyn Iterator<Item = (&RangeInclusive<i32>, &char)>| -> String {
+        it.map(|(_, v)| *v).collect()
+    };
+    assert_eq!(values(&mut map.overlapping(2..=2)), "ab");
+    assert_eq!(values(&mut map.overlapping(3..5)), "b");
+    assert_eq!(values(&mut map.overlapping(4..=5)), "bc");
+    assert_eq!(values(&mut map.overlapping(..)), "abcd");
+    assert_eq!(values(&mut map.overlapping(10..)), "");
+    assert_eq!(
+        values(&mut map.overlapping((Bound::Excluded(4), Bound::Unbounded))),
+        "cd"
+    );
+    assert_eq!(values(&mut map.stabbing(&6)), "cd");
+    assert_eq!(values(&mut map.stabbing(&2)), "ab");
+    assert_eq!(values(&mut map.stabbing(&-1)), "");
+}
+
+#[test]
+#[should_panic]
+fn insert_reversed_interval() {
+    let mut map = IntervalTreeMap::new();
+    #[allow(clippy::reversed_empty_ranges)]
+    map.insert(3..=1, ());
+}
+
+fn to_intervals(v: &[(i8, u8)]) -> Vec<RangeInclusive<i32>> {
+    v.iter()
+        .map(|&(start, len)| start as i32..=start as i32 + len as i32 / 8)
+        .collect()
+}
+
+fn brute_force<R: RangeBounds<i32>>(
+    intervals: &[RangeInclusive<i32>],
+    range: &R,
+) -> Vec<RangeInclusive<i32>> {
+    let mut found: Vec<_> = intervals
+        .iter()
+        .filter(|r| {
+            let reaches_start = match range.start_bound() {
+                Bound::Included(s) => r.end() >= s,
+                Bound::Excluded(s) => r.end() > s,
+                Bound::Unbounded => true,
+            };
+            let starts_before_end = match range.end_bound() {
+                Bound::Included(e) => r.start() <= e,
+                Bound::Excluded(e) => r.start() < e,
+                Bound::Unbounded => true,
+            };
+            reaches_start && starts_before_end
+        })
+        .cloned()
+        .collect();
+    found.sort_by_key(|r| (*r.start(), *r.end()));
+    found.dedup();
+    found
+}
+
+fn same_overlaps<R: RangeBounds<i32>>(
+    map: &IntervalTreeMap<i32, ()>,
+    intervals: &[RangeInclusive<i32>],
+    range: R,
+) -> bool {
+    let expected = brute_force(intervals, &range);
+    map.overlapping(range)
+        .map(|(r, _)| r.clone())
+        .collect::<Vec<_>>()
+        == expected
+}
+
+#[quickcheck]
+fn overlapping_like_brute_force(
+    v: Vec<(i8, u8)>,
+    removed: Vec<(i8, u8)>,
+    start: i8,
+    len: u8,
+) -> bool {
+    let mut intervals = to_intervals(&v);
+    let mut map: IntervalTreeMap<i32, ()> = intervals.iter().map(|r| (r.clone(), ())).collect();
+    for r in to_intervals(&removed) {
+        map.remove(&r);
+        intervals.retain(|x| *x != r);
+    }
+
+    let start = start as i32;
+    let end = start + len as i32 / 4;
+    same_overlaps(&map, &intervals, start..=end)
+        && same_overlaps(&map, &intervals, start..end)
+        && same_overlaps(&map, &intervals, start..)
+        && same_overlaps(&map, &intervals, ..end)
+        && map
+            .stabbing(&start)
+            .map(|(r, _)| r.clone())
+            .collect::<Vec<_>>()
+            == brute_force(&intervals, &(start..=start))
+}
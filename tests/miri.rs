@@ -0,0 +1,200 @@
+// Small, deterministic tests of the unsafe code in RBTreeMap and IntervalTreeMap, sized to run
+// quickly under Miri with Stacked Borrows:
+//
+//     cargo +nightly miri test --test miri
+//
+// They also run as part of the normal test suite.
+use dsgym_rs::interval_tree::IntervalTreeMap;
+use dsgym_rs::rbtree::{Entry, RBTreeMap};
+use std::cell::Cell;
+use std::ops::Bound;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+
+// A mix of insertions in an order that makes every rebalancing case happen.
+fn keys() -> Vec<i32> {
+    (0..40).map(|x| (x * 17) % 41).collect()
+}
+
+#[test]
+fn insert_remove_drop() {
+    let counter = Rc::new(());
+    let mut map = RBTreeMap::new();
+    for k in keys() {
+        assert!(map.insert(k, counter.clone()).is_none());
+    }
+    assert!(map.insert(5, counter.clone()).is_some());
+    for k in keys().into_iter().step_by(2) {
+        assert!(map.remove(&k).is_some());
+    }
+    assert_eq!(map.len(), 20);
+    assert_eq!(Rc::strong_count(&counter), 21);
+    drop(map);
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn iterate() {
+    let mut map: RBTreeMap<i32, String> = keys().into_iter().map(|k| (k, k.to_string())).collect();
+
+    let mut it = map.iter();
+    let first = it.next().unwrap();
+    let last = it.next_back().unwrap();
+    assert!(first.0 < last.0);
+    assert_eq!(it.len(), 38);
+
+    for (k, v) in map.iter_mut() {
+        v.push_str(&k.to_string());
+    }
+    for (_, v) in map.range_mut(10..20).rev() {
+        v.push('!');
+    }
+    assert_eq!(map.get(&12).map(|s| s.as_str()), Some("1212!"));
+    assert_eq!(map.values().filter(|v| v.ends_with('!')).count(), 10);
+
+    // partially consumed owning iterator frees the rest on drop
+    let mut into = map.clone().into_iter();
+    assert_eq!(into.next().map(|(k, _)| k), Some(0));
+    assert_eq!(into.next_back().map(|(k, _)| k), Some(40));
+    drop(into);
+    assert_eq!(map.into_values().count(), 40);
+}
+
+#[test]
+fn entries_and_cursors() {
+    let mut map = RBTreeMap::new();
+    for k in keys() {
+        *map.entry(k % 7 * 10).or_insert(0) += 1;
+    }
+    match map.entry(30) {
+        Entry::Occupied(e) => assert_eq!(e.remove_entry().0, 30),
+        Entry::Vacant(_) => unreachable!(),
+    }
+
+    let mut cursor = map.lower_bound_mut(Bound::Included(&25));
+    cursor.insert_before(30, 300);
+    cursor.insert_after(45, 450);
+    assert_eq!(cursor.remove_current().map(|(k, _)| k), Some(40));
+    assert_eq!(cursor.key(), Some(&45));
+    *cursor.value_mut().unwrap() += 1;
+    for _ in 0..5 {
+        cursor.move_prev();
+    }
+    assert_eq!(cursor.key(), None);
+    assert_eq!(map.get(&45), Some(&451));
+
+    let odd: Vec<_> = map.extract_if(|k, _| k % 20 != 0).map(|(k, _)| k).collect();
+    assert_eq!(odd, vec![10, 30, 45, 50]);
+    map.retain(|k, _| *k != 40);
+    assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![0, 20, 60]);
+}
+
+#[test]
+fn split_and_append() {
+    let mut map: RBTreeMap<_, _> = keys().into_iter().map(|k| (k, Box::new(k))).collect();
+    let mut right = map.split_off(&20);
+    assert_eq!((map.len(), right.len()), (20, 20));
+    right.append(&mut map);
+    assert!(map.is_empty());
+    let mut high = right.split_off(&30);
+    let (key, value) = high.pop_first().unwrap();
+    let joined = RBTreeMap::join(right, key, value, high);
+    assert_eq!(joined.len(), 40);
+    assert_eq!(joined.clone(), joined);
+
+    let sorted = RBTreeMap::from_sorted_vec((0..20).map(|k| (k, Box::new(k))).collect());
+    let sorted = RBTreeMap::from_sorted_iter(
+        sorted
+            .into_iter()
+            .rev()
+            .skip(3)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev(),
+    );
+    assert_eq!(sorted.last_key_value().map(|(k, _)| *k), Some(16));
+}
+
+#[test]
+fn split_off_with_panicking_comparator() {
+    let calls = Cell::new(0);
+    let cmp = |a: &i32, b: &i32| {
+        calls.set(calls.get() + 1);
+        if calls.get() > 200 {
+            panic!("comparator panicked");
+        }
+        a.cmp(b)
+    };
+    let mut map = RBTreeMap::with_comparator(cmp);
+    for k in keys() {
+        map.insert(k, Box::new(k));
+    }
+    // fails on the third comparison of the split
+    calls.set(198);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| map.split_off(&20)));
+    assert!(result.is_err());
+    calls.set(0);
+
+    // the map is left as it was
+    let mut expected = keys();
+    expected.sort();
+    assert_eq!(map.len(), 40);
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, **v))
+        .eq(expected.into_iter().map(|k| (k, k))));
+    assert_eq!(map.split_off(&20).len(), 20);
+}
+
+#[test]
+fn send_and_sync() {
+    fn is_send<T: Send>() {}
+    fn is_sync<T: Sync>() {}
+    is_send::<RBTreeMap<i32, String>>();
+    is_sync::<RBTreeMap<i32, String>>();
+    is_send::<dsgym_rs::rbtree::Iter<'static, i32, String>>();
+    is_sync::<dsgym_rs::rbtree::IterMut<'static, i32, String>>();
+    is_send::<dsgym_rs::rbtree::IntoIter<i32, String>>();
+
+    let map: Arc<RBTreeMap<i32, i32>> = Arc::new(keys().into_iter().map(|k| (k, k)).collect());
+    let handles: Vec<_> = (0..2)
+        .map(|i| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || map.range(i * 10..).map(|(_, v)| v).sum::<i32>())
+        })
+        .collect();
+    let sums: Vec<i32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(sums[0], (0..41).sum::<i32>() - 24);
+
+    let mut map = Arc::try_unwrap(map).unwrap();
+    thread::scope(|s| {
+        s.spawn(|| {
+            for (_, v) in map.iter_mut() {
+                *v *= 2;
+            }
+        });
+    });
+    let owned = thread::spawn(move || map.into_iter().map(|(_, v)| v).max())
+        .join()
+        .unwrap();
+    assert_eq!(owned, Some(80));
+}
+
+#[test]
+fn intervals() {
+    let counter = Rc::new(());
+    let mut map = IntervalTreeMap::new();
+    for k in keys() {
+        assert!(map.insert(k..=k + k % 5, counter.clone()).is_none());
+    }
+    for k in keys().into_iter().step_by(3) {
+        assert!(map.remove(&(k..=k + k % 5)).is_some());
+    }
+    assert_eq!(map.stabbing(&22).count(), 2);
+    assert_eq!(map.overlapping(10..20).count(), 7);
+    assert_eq!(Rc::strong_count(&counter), 27);
+    drop(map);
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
@@ -1,5 +1,8 @@
+#![allow(clippy::bool_assert_comparison, clippy::len_zero)]
+
 use dsgym_rs::rbtree::RBTreeMap;
 use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
 
 extern crate quickcheck;
 #[macro_use(quickcheck)]
@@ -38,6 +41,7 @@ fn iter() {
 }
 
 #[quickcheck]
+#[allow(unused_variables, unused_mut)]
 fn size_is_set_properly(v: Vec<i32>) -> bool {
     let mut hashmap = HashMap::new();
     let mut map = RBTreeMap::new();
@@ -101,3 +105,752 @@ fn insert_and_remove(v: Vec<i32>) -> bool {
     }
     true
 }
+
+#[test]
+fn range() {
+    let mut map = RBTreeMap::new();
+    for x in 0..10 {
+        map.insert(x, x * 10);
+    }
+    let keys = |r: dsgym_rs::rbtree::Range<i32, i32>| r.map(|(&k, _)| k).collect::<Vec<_>>();
+    assert_eq!(keys(map.range(3..6)), vec![3, 4, 5]);
+    assert_eq!(keys(map.range(3..=6)), vec![3, 4, 5, 6]);
+    assert_eq!(keys(map.range(..2)), vec![0, 1]);
+    assert_eq!(keys(map.range(8..)), vec![8, 9]);
+    assert_eq!(keys(map.range(20..)), Vec::<i32>::new());
+    assert_eq!(keys(map.range(4..4)), Vec::<i32>::new());
+    assert_eq!(map.range(..).count(), 10);
+
+    for (_, v) in map.range_mut(5..7) {
+        *v += 1;
+    }
+    assert_eq!(map.get(&5), Some(&51));
+    assert_eq!(map.get(&6), Some(&61));
+    assert_eq!(map.get(&7), Some(&70));
+}
+
+#[test]
+#[should_panic]
+fn range_start_greater_than_end() {
+    let mut map = RBTreeMap::new();
+    map.insert(1, 1);
+    map.range((Bound::Included(5), Bound::Excluded(3)));
+}
+
+#[quickcheck]
+fn range_like_btreemap(v: Vec<i32>, start: i32, end: i32, inclusive: bool) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+
+    for &x in v.iter() {
+        btmap.insert(x, x);
+        rbtmap.insert(x, x);
+    }
+
+    let (start, end) = (start.min(end), start.max(end));
+    if inclusive {
+        btmap.range(start..=end).eq(rbtmap.range(start..=end))
+    } else {
+        btmap.range(start..end).eq(rbtmap.range(start..end))
+            && btmap.range(..end).eq(rbtmap.range(..end))
+            && btmap.range(start..).eq(rbtmap.range(start..))
+    }
+}
+
+#[test]
+fn entry() {
+    use dsgym_rs::rbtree::Entry;
+
+    let mut map = RBTreeMap::new();
+    for w in "a b a c b a".split(' ') {
+        *map.entry(w).or_insert(0) += 1;
+    }
+    assert_eq!(map.get(&"a"), Some(&3));
+    assert_eq!(map.get(&"b"), Some(&2));
+    assert_eq!(map.get(&"c"), Some(&1));
+
+    map.entry("c").and_modify(|v| *v += 10).or_insert(0);
+    map.entry("d").and_modify(|v| *v += 10).or_insert_with(|| 7);
+    assert_eq!(map.get(&"c"), Some(&11));
+    assert_eq!(map.get(&"d"), Some(&7));
+    assert_eq!(map.len(), 4);
+
+    match map.entry("b") {
+        Entry::Occupied(mut e) => {
+            assert_eq!(e.insert(20), 2);
+            assert_eq!(e.remove_entry(), ("b", 20));
+        }
+        Entry::Vacant(_) => unreachable!(),
+    }
+    match map.entry("e") {
+        Entry::Occupied(_) => unreachable!(),
+        Entry::Vacant(e) => assert_eq!(*e.insert(5), 5),
+    }
+    assert_eq!(map.len(), 4);
+    assert!(!map.contains_key(&"b"));
+    assert_eq!(map.get(&"e"), Some(&5));
+}
+
+#[quickcheck]
+fn entry_like_btreemap(v: Vec<(u8, i32)>) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+
+    for &(k, x) in v.iter() {
+        *btmap.entry(k).or_insert(0) += x as i64;
+        *rbtmap.entry(k).or_insert(0) += x as i64;
+    }
+    btmap.len() == rbtmap.len() && btmap.iter().eq(rbtmap.iter())
+}
+
+#[test]
+fn remove_without_default() {
+    use std::rc::Rc;
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+    struct Key(i32);
+    type Value = (Rc<()>, Box<dyn Fn(i32) -> i32>);
+
+    let counter = Rc::new(());
+    let mut map: RBTreeMap<Key, Value> = RBTreeMap::new();
+    for x in 0..100 {
+        map.insert(Key(x), (counter.clone(), Box::new(move |y| x + y)));
+    }
+    assert_eq!(Rc::strong_count(&counter), 101);
+
+    for x in (0..100).step_by(3) {
+        let (k, (_, f)) = map.remove_entry(&Key(x)).unwrap();
+        assert_eq!(k, Key(x));
+        assert_eq!(f(1), x + 1);
+    }
+    assert!(map.remove(&Key(0)).is_none());
+    assert_eq!(map.len(), 66);
+    assert_eq!(Rc::strong_count(&counter), 67);
+    for (k, (_, f)) in map.iter() {
+        assert_eq!(f(0), k.0);
+    }
+
+    drop(map);
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn iterators() {
+    let mut map = RBTreeMap::new();
+    for x in [5, 3, 8, 1, 4, 7, 9, 2, 6] {
+        map.insert(x, x * 10);
+    }
+
+    assert_eq!(
+        map.keys().copied().collect::<Vec<_>>(),
+        (1..10).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        map.values().copied().collect::<Vec<_>>(),
+        (1..10).map(|x| x * 10).collect::<Vec<_>>()
+    );
+
+    for (k, v) in map.iter_mut() {
+        *v += k;
+    }
+    for v in map.values_mut() {
+        *v += 1;
+    }
+    for (_, v) in &mut map {
+        *v *= 2;
+    }
+    for (k, v) in &map {
+        assert_eq!(*v, (k * 11 + 1) * 2);
+    }
+
+    let entries: Vec<_> = map.into_iter().collect();
+    assert_eq!(entries.len(), 9);
+    assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+}
+
+#[test]
+fn into_iter_partially_consumed() {
+    use std::rc::Rc;
+
+    let counter = Rc::new(());
+    let mut map = RBTreeMap::new();
+    for x in 0..1000 {
+        map.insert(x, counter.clone());
+    }
+    let mut it = map.into_iter();
+    assert_eq!(it.next().map(|(k, _)| k), Some(0));
+    assert_eq!(it.nth(10).map(|(k, _)| k), Some(11));
+    drop(it);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    let mut map = RBTreeMap::new();
+    for x in 0..10 {
+        map.insert(x.to_string(), x);
+    }
+    assert_eq!(
+        map.into_keys().collect::<Vec<_>>(),
+        vec!["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]
+    );
+}
+
+#[quickcheck]
+fn into_iter_like_btreemap(v: Vec<i32>) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+
+    for &x in v.iter() {
+        btmap.insert(x, x.wrapping_mul(3));
+        rbtmap.insert(x, x.wrapping_mul(3));
+    }
+    btmap.into_values().eq(rbtmap.into_values())
+}
+
+#[test]
+fn double_ended_iter() {
+    let mut map = RBTreeMap::new();
+    for x in 0..10 {
+        map.insert(x, x);
+    }
+    let mut it = map.iter();
+    assert_eq!(it.len(), 10);
+    assert_eq!(it.next(), Some((&0, &0)));
+    assert_eq!(it.next_back(), Some((&9, &9)));
+    assert_eq!(it.len(), 8);
+    assert_eq!(
+        it.rev().take(3).map(|(&k, _)| k).collect::<Vec<_>>(),
+        vec![8, 7, 6]
+    );
+
+    assert_eq!(map.keys().last(), Some(&9));
+    assert_eq!(map.range(2..5).last(), Some((&4, &4)));
+    assert_eq!(
+        map.range(2..5).rev().map(|(&k, _)| k).collect::<Vec<_>>(),
+        vec![4, 3, 2]
+    );
+
+    let mut range = map.range(4..=5);
+    assert_eq!(range.next(), Some((&4, &4)));
+    assert_eq!(range.next_back(), Some((&5, &5)));
+    assert_eq!(range.next(), None);
+    assert_eq!(range.next_back(), None);
+
+    let mut it = map.into_iter();
+    assert_eq!(it.next_back(), Some((9, 9)));
+    assert_eq!(it.next(), Some((0, 0)));
+    assert_eq!(it.len(), 8);
+    assert_eq!(
+        it.rev().map(|(k, _)| k).collect::<Vec<_>>(),
+        vec![8, 7, 6, 5, 4, 3, 2, 1]
+    );
+}
+
+#[quickcheck]
+fn double_ended_like_btreemap(v: Vec<i32>, ends: Vec<bool>) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+    let mut owned = RBTreeMap::new();
+
+    for &x in v.iter() {
+        btmap.insert(x, x);
+        rbtmap.insert(x, x);
+        owned.insert(x, x);
+    }
+
+    let mut btit = btmap.iter();
+    let mut rbtit = rbtmap.iter();
+    let mut btinto = btmap.clone().into_iter();
+    let mut rbtinto = owned.into_iter();
+    for &from_back in ends.iter().chain(std::iter::repeat_n(&true, v.len() + 1)) {
+        if btit.len() != rbtit.len() || btinto.len() != rbtinto.len() {
+            return false;
+        }
+        let same = if from_back {
+            btit.next_back() == rbtit.next_back() && btinto.next_back() == rbtinto.next_back()
+        } else {
+            btit.next() == rbtit.next() && btinto.next() == rbtinto.next()
+        };
+        if !same {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn borrowed_lookups() {
+    let mut map: RBTreeMap<String, Vec<i32>> = RBTreeMap::new();
+    for w in ["pear", "apple", "fig", "kiwi"] {
+        map.insert(w.to_string(), vec![]);
+    }
+
+    assert!(map.contains_key("fig"));
+    assert!(!map.contains_key("plum"));
+    map.get_mut("kiwi").unwrap().push(1);
+    assert_eq!(map.get("kiwi"), Some(&vec![1]));
+    assert_eq!(
+        map.get_key_value("apple").map(|(k, _)| k.as_str()),
+        Some("apple")
+    );
+    assert_eq!(
+        map.range::<str, _>((Bound::Included("b"), Bound::Excluded("l")))
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>(),
+        vec!["fig", "kiwi"]
+    );
+    assert_eq!(map.remove("pear"), Some(vec![]));
+    assert_eq!(
+        map.remove_entry("fig").map(|(k, _)| k),
+        Some("fig".to_string())
+    );
+    assert_eq!(map.len(), 2);
+}
+
+#[test]
+fn order_statistics() {
+    let mut map = RBTreeMap::new();
+    for x in (0..100).rev() {
+        map.insert(x * 2, x);
+    }
+    assert_eq!(map.select(0), Some((&0, &0)));
+    assert_eq!(map.select(10), Some((&20, &10)));
+    assert_eq!(map.select(99), Some((&198, &99)));
+    assert_eq!(map.select(100), None);
+    assert_eq!(map.rank(&0), 0);
+    assert_eq!(map.rank(&21), 11);
+    assert_eq!(map.rank(&1000), 100);
+    assert_eq!(map.count_in_range(10..20), 5);
+    assert_eq!(map.count_in_range(10..=20), 6);
+    assert_eq!(map.count_in_range(..), 100);
+    assert_eq!(map.count_in_range(500..), 0);
+}
+
+#[quickcheck]
+fn order_statistics_like_btreemap(v: Vec<i16>, probe: i16, width: u8) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+
+    for &x in v.iter() {
+        btmap.insert(x, ());
+        rbtmap.insert(x, ());
+    }
+    for &x in v.iter().step_by(2) {
+        btmap.remove(&x);
+        rbtmap.remove(&x);
+    }
+
+    let end = probe.saturating_add(width as i16);
+    btmap
+        .keys()
+        .enumerate()
+        .all(|(i, k)| rbtmap.select(i) == Some((k, &())))
+        && rbtmap.select(btmap.len()).is_none()
+        && rbtmap.rank(&probe) == btmap.range(..probe).count()
+        && rbtmap.count_in_range(probe..end) == btmap.range(probe..end).count()
+        && rbtmap.count_in_range(probe..=end) == btmap.range(probe..=end).count()
+}
+
+#[test]
+fn neighbours() {
+    let mut map = RBTreeMap::new();
+    assert_eq!(map.first_key_value(), None);
+    assert_eq!(map.pop_last(), None);
+    for x in [10, 20, 30, 40] {
+        map.insert(x, x / 10);
+    }
+    assert_eq!(map.floor(&25), Some((&20, &2)));
+    assert_eq!(map.floor(&20), Some((&20, &2)));
+    assert_eq!(map.floor(&5), None);
+    assert_eq!(map.lower(&20), Some((&10, &1)));
+    assert_eq!(map.ceiling(&25), Some((&30, &3)));
+    assert_eq!(map.ceiling(&30), Some((&30, &3)));
+    assert_eq!(map.ceiling(&45), None);
+    assert_eq!(map.higher(&30), Some((&40, &4)));
+    assert_eq!(map.first_key_value(), Some((&10, &1)));
+    assert_eq!(map.last_key_value(), Some((&40, &4)));
+    assert_eq!(map.pop_first(), Some((10, 1)));
+    assert_eq!(map.pop_last(), Some((40, 4)));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.first_key_value(), Some((&20, &2)));
+}
+
+#[quickcheck]
+fn neighbours_like_btreemap(v: Vec<i8>, probe: i8) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+
+    for &x in v.iter() {
+        btmap.insert(x, x);
+        rbtmap.insert(x, x);
+    }
+
+    if rbtmap.floor(&probe) != btmap.range(..=probe).next_back()
+        || rbtmap.lower(&probe) != btmap.range(..probe).next_back()
+        || rbtmap.ceiling(&probe) != btmap.range(probe..).next()
+        || rbtmap.higher(&probe)
+            != btmap
+                .range((Bound::Excluded(probe), Bound::Unbounded))
+                .next()
+    {
+        return false;
+    }
+    while !btmap.is_empty() {
+        if rbtmap.first_key_value() != btmap.first_key_value()
+            || rbtmap.last_key_value() != btmap.last_key_value()
+        {
+            return false;
+        }
+        let popped = if probe % 2 == 0 {
+            rbtmap.pop_first() == btmap.pop_first()
+        } else {
+            rbtmap.pop_last() == btmap.pop_last()
+        };
+        if !popped {
+            return false;
+        }
+    }
+    rbtmap.is_empty()
+}
+
+#[test]
+fn split_off_and_append() {
+    let mut map = RBTreeMap::new();
+    for x in 0..100 {
+        map.insert(x, x);
+    }
+    let mut right = map.split_off(&60);
+    assert_eq!(map.len(), 60);
+    assert_eq!(right.len(), 40);
+    assert_eq!(map.last_key_value(), Some((&59, &59)));
+    assert_eq!(right.first_key_value(), Some((&60, &60)));
+    assert_eq!(right.select(10), Some((&70, &70)));
+
+    let mut high = right.split_off(&1000);
+    assert!(high.is_empty());
+    map.append(&mut high);
+    map.append(&mut right);
+    assert!(right.is_empty());
+    assert_eq!(map.len(), 100);
+    assert!(map.keys().copied().eq(0..100));
+
+    let joined = RBTreeMap::join(RBTreeMap::new(), -1, -1, map.split_off(&50));
+    assert_eq!(joined.len(), 51);
+    assert_eq!(joined.first_key_value(), Some((&-1, &-1)));
+}
+
+#[test]
+#[should_panic]
+fn join_unordered() {
+    let mut left = RBTreeMap::new();
+    left.insert(10, ());
+    RBTreeMap::join(left, 5, (), RBTreeMap::new());
+}
+
+#[quickcheck]
+fn split_off_and_append_like_btreemap(v: Vec<i32>, w: Vec<i32>, at: i32) -> bool {
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+    for &x in v.iter() {
+        btmap.insert(x, x);
+        rbtmap.insert(x, x);
+    }
+
+    let mut btright = btmap.split_off(&at);
+    let mut rbtright = rbtmap.split_off(&at);
+    if !btmap.iter().eq(rbtmap.iter()) || !btright.iter().eq(rbtright.iter()) {
+        return false;
+    }
+
+    let mut btother: BTreeMap<i32, i32> = w.iter().map(|&x| (x, x.wrapping_neg())).collect();
+    let mut rbtother = RBTreeMap::new();
+    for &x in w.iter() {
+        rbtother.insert(x, x.wrapping_neg());
+    }
+    btright.append(&mut btmap);
+    rbtright.append(&mut rbtmap);
+    btright.append(&mut btother);
+    rbtright.append(&mut rbtother);
+    btright.len() == rbtright.len() && btright.iter().eq(rbtright.iter()) && rbtmap.is_empty()
+}
+
+#[test]
+fn cursor() {
+    let mut map = RBTreeMap::new();
+    for x in [10, 20, 30, 40] {
+        map.insert(x, x);
+    }
+
+    let mut cursor = map.lower_bound(Bound::Included(&15));
+    assert_eq!(cursor.key(), Some(&20));
+    assert_eq!(cursor.peek_prev(), Some((&10, &10)));
+    assert_eq!(cursor.peek_next(), Some((&30, &30)));
+    cursor.move_next();
+    cursor.move_next();
+    assert_eq!(cursor.key(), Some(&40));
+    cursor.move_next();
+    assert_eq!(cursor.key(), None);
+    assert_eq!(cursor.peek_next(), Some((&10, &10)));
+    cursor.move_prev();
+    assert_eq!(cursor.key(), Some(&40));
+
+    assert_eq!(map.lower_bound(Bound::Excluded(&20)).key(), Some(&30));
+    assert_eq!(map.upper_bound(Bound::Included(&20)).key(), Some(&20));
+    assert_eq!(map.upper_bound(Bound::Excluded(&20)).key(), Some(&10));
+    assert_eq!(map.upper_bound(Bound::Excluded(&10)).key(), None);
+    assert_eq!(map.upper_bound::<i32>(Bound::Unbounded).key(), Some(&40));
+
+    let mut cursor = map.lower_bound_mut(Bound::Included(&20));
+    cursor.insert_before(15, 15);
+    cursor.insert_after(25, 25);
+    assert_eq!(cursor.key(), Some(&20));
+    *cursor.value_mut().unwrap() += 1;
+    assert_eq!(cursor.remove_current(), Some((20, 21)));
+    assert_eq!(cursor.key(), Some(&25));
+    assert_eq!(cursor.remove_current_and_move_back(), Some((25, 25)));
+    assert_eq!(cursor.key(), Some(&15));
+    cursor.move_prev();
+    cursor.move_prev();
+    assert_eq!(cursor.key(), None);
+    cursor.insert_after(5, 5);
+    cursor.insert_before(50, 50);
+    assert_eq!(cursor.remove_current(), None);
+
+    assert_eq!(
+        map.keys().copied().collect::<Vec<_>>(),
+        vec![5, 10, 15, 30, 40, 50]
+    );
+}
+
+#[test]
+#[should_panic]
+fn cursor_insert_unordered() {
+    let mut map = RBTreeMap::new();
+    map.insert(10, ());
+    map.insert(20, ());
+    let mut cursor = map.lower_bound_mut(Bound::Included(&10));
+    cursor.insert_after(25, ());
+}
+
+#[quickcheck]
+fn cursor_walk_like_iter(v: Vec<i32>, probe: i32) -> bool {
+    let mut map = RBTreeMap::new();
+    for &x in v.iter() {
+        map.insert(x, x);
+    }
+
+    let mut cursor = map.lower_bound(Bound::Included(&probe));
+    let mut forward = Vec::new();
+    while let Some((k, _)) = cursor.key_value() {
+        forward.push(*k);
+        cursor.move_next();
+    }
+    let mut cursor = map.upper_bound(Bound::Excluded(&probe));
+    let mut backward = Vec::new();
+    while let Some(k) = cursor.key() {
+        backward.push(*k);
+        cursor.move_prev();
+    }
+    forward.iter().eq(map.range(probe..).map(|(k, _)| k))
+        && backward.iter().eq(map.range(..probe).rev().map(|(k, _)| k))
+}
+
+#[test]
+fn extract_if() {
+    let mut map = RBTreeMap::new();
+    for x in 0..10 {
+        map.insert(x, x * 10);
+    }
+    {
+        let mut evens = map.extract_if(|k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+        assert_eq!(evens.next(), Some((0, 1)));
+        assert_eq!(evens.next(), Some((2, 21)));
+    }
+    assert_eq!(map.len(), 8);
+    // extraction is lazy, keys after 2 have not been visited
+    assert_eq!(map.get(&1), Some(&11));
+    assert_eq!(map.get(&3), Some(&30));
+
+    map.retain(|&k, _| k > 4);
+    assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![5, 6, 7, 8, 9]);
+}
+
+#[quickcheck]
+fn retain_like_btreemap(v: Vec<i32>, m: u8) -> bool {
+    let m = m as i32 + 1;
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::new();
+    for &x in v.iter() {
+        btmap.insert(x, x);
+        rbtmap.insert(x, x);
+    }
+
+    btmap.retain(|k, _| k % m != 0);
+    let extracted: Vec<_> = rbtmap.extract_if(|k, _| k % m == 0).collect();
+    btmap.iter().eq(rbtmap.iter())
+        && extracted.iter().all(|(k, v)| k % m == 0 && k == v)
+        && extracted.len() + rbtmap.len()
+            == v.iter().collect::<std::collections::HashSet<_>>().len()
+}
+
+#[test]
+fn std_traits() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let map: RBTreeMap<i32, &str> = [(3, "c"), (1, "a"), (2, "b")].into_iter().collect();
+    assert_eq!(format!("{:?}", map), r#"{1: "a", 2: "b", 3: "c"}"#);
+    assert_eq!(map[&2], "b");
+
+    let mut copy = map.clone();
+    assert_eq!(map, copy);
+    copy.insert(4, "d");
+    assert_ne!(map, copy);
+    assert!(map < copy);
+    copy.extend([(&0, &"z")]);
+    assert_eq!(copy.first_key_value(), Some((&0, &"z")));
+    copy.remove(&0);
+    copy.remove(&4);
+    assert_eq!(map.cmp(&copy), std::cmp::Ordering::Equal);
+
+    let hash = |m: &RBTreeMap<i32, &str>| {
+        let mut h = DefaultHasher::new();
+        m.hash(&mut h);
+        h.finish()
+    };
+    assert_eq!(hash(&map), hash(&copy));
+    assert_eq!(RBTreeMap::<i32, i32>::default(), RBTreeMap::new());
+}
+
+#[test]
+#[should_panic]
+fn index_missing_key() {
+    let map: RBTreeMap<i32, i32> = RBTreeMap::new();
+    let _ = map[&1];
+}
+
+#[quickcheck]
+fn traits_like_btreemap(v: Vec<(i8, i8)>, w: Vec<(i8, i8)>) -> bool {
+    let bt1: BTreeMap<_, _> = v.iter().copied().collect();
+    let bt2: BTreeMap<_, _> = w.iter().copied().collect();
+    let rbt1: RBTreeMap<_, _> = v.iter().copied().collect();
+    let rbt2: RBTreeMap<_, _> = w.iter().copied().collect();
+
+    format!("{:?}", bt1) == format!("{:?}", rbt1)
+        && (bt1 == bt2) == (rbt1 == rbt2)
+        && bt1.cmp(&bt2) == rbt1.cmp(&rbt2)
+        && rbt1.clone() == rbt1
+}
+
+#[test]
+fn from_sorted() {
+    let map = RBTreeMap::from_sorted_vec(vec![(1, "a"), (2, "b"), (2, "c"), (5, "d")]);
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get(&2), Some(&"c"));
+
+    let mut map = RBTreeMap::from_sorted_iter((0..10_000).map(|x| (x, x * 2)));
+    assert_eq!(map.len(), 10_000);
+    assert_eq!(map.select(5_000), Some((&5_000, &10_000)));
+    map.insert(-1, -2);
+    assert_eq!(map.remove(&9_999), Some(19_998));
+    assert_eq!(map.first_key_value(), Some((&-1, &-2)));
+}
+
+#[test]
+#[should_panic(expected = "sorted")]
+fn from_sorted_unsorted() {
+    RBTreeMap::from_sorted_iter(vec![(1, ()), (3, ()), (2, ())]);
+}
+
+#[quickcheck]
+fn from_sorted_like_btreemap(v: Vec<(i8, i32)>) -> bool {
+    let mut v = v;
+    v.sort_by_key(|&(k, _)| k);
+    let btmap: BTreeMap<_, _> = v.iter().copied().collect();
+    let rbtmap = RBTreeMap::from_sorted_vec(v);
+    btmap.len() == rbtmap.len() && btmap.iter().eq(rbtmap.iter())
+}
+
+#[test]
+fn comparator() {
+    use dsgym_rs::comparator::Comparator;
+    use std::cmp::Ordering;
+
+    // orders strings ignoring ASCII case, for both owned and borrowed lookups
+    struct CaseInsensitive;
+    impl Comparator<str> for CaseInsensitive {
+        fn compare(&self, a: &str, b: &str) -> Ordering {
+            a.bytes()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase()))
+        }
+    }
+    impl Comparator<String> for CaseInsensitive {
+        fn compare(&self, a: &String, b: &String) -> Ordering {
+            Comparator::<str>::compare(self, a, b)
+        }
+    }
+
+    let mut map = RBTreeMap::with_comparator(CaseInsensitive);
+    map.insert("banana".to_string(), 1);
+    map.insert("Apple".to_string(), 2);
+    assert_eq!(map.insert("APPLE".to_string(), 3), Some(2));
+    map.insert("cherry".to_string(), 4);
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get("apple"), Some(&3));
+    assert_eq!(
+        map.get_key_value("BANANA"),
+        Some((&"banana".to_string(), &1))
+    );
+    assert!(map.keys().eq(["Apple", "banana", "cherry"]));
+    let bounds = (Bound::Included("B"), Bound::Excluded("D"));
+    assert_eq!(map.range::<str, _>(bounds).count(), 2);
+    assert_eq!(map.remove("CHERRY"), Some(4));
+
+    let mut reversed = RBTreeMap::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+    reversed.extend((0..10).map(|x| (x, x * x)));
+    assert!(reversed.keys().copied().eq((0..10).rev()));
+    assert_eq!(reversed.first_key_value(), Some((&9, &81)));
+    let bounds = (Bound::Included(7), Bound::Included(2));
+    assert!(reversed
+        .range(bounds)
+        .map(|(&k, _)| k)
+        .eq([7, 6, 5, 4, 3, 2]));
+    assert_eq!(reversed.floor(&4), Some((&4, &16)));
+    assert_eq!(reversed.lower(&4), Some((&5, &25)));
+    assert_eq!(reversed.rank(&7), 2);
+    let low = reversed.split_off(&4);
+    assert!(low.keys().copied().eq((0..=4).rev()));
+    assert!(reversed.keys().copied().eq((5..10).rev()));
+
+    let mut floats = RBTreeMap::with_comparator(|a: &f64, b: &f64| a.total_cmp(b));
+    for x in [2.5, f64::NAN, -0.0, 0.0, f64::NEG_INFINITY, 1.0] {
+        floats.insert(x, ());
+    }
+    assert_eq!(floats.len(), 6);
+    assert_eq!(
+        floats.first_key_value().map(|(&k, _)| k),
+        Some(f64::NEG_INFINITY)
+    );
+    assert!(floats.last_key_value().unwrap().0.is_nan());
+    assert!(floats.contains_key(&-0.0));
+}
+
+#[quickcheck]
+fn reverse_comparator_like_btreemap(v: Vec<(i8, i32)>, w: Vec<i8>) -> bool {
+    use std::cmp::Reverse;
+
+    let mut btmap = BTreeMap::new();
+    let mut rbtmap = RBTreeMap::with_comparator(|a: &i8, b: &i8| b.cmp(a));
+    for &(k, x) in v.iter() {
+        btmap.insert(Reverse(k), x);
+        rbtmap.insert(k, x);
+    }
+    for &k in w.iter() {
+        if btmap.remove(&Reverse(k)) != rbtmap.remove(&k) {
+            return false;
+        }
+    }
+    btmap.len() == rbtmap.len() && btmap.iter().map(|(Reverse(k), v)| (k, v)).eq(rbtmap.iter())
+}
@@ -0,0 +1,127 @@
+use dsgym_rs::rbtree_set::RBTreeSet;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+extern crate quickcheck;
+#[macro_use(quickcheck)]
+extern crate quickcheck_macros;
+
+#[test]
+fn basics() {
+    let mut set = RBTreeSet::new();
+    assert!(set.insert(3));
+    assert!(set.insert(1));
+    assert!(set.insert(2));
+    assert!(!set.insert(2));
+    assert_eq!(set.len(), 3);
+    assert!(set.contains(&1));
+    assert!(!set.contains(&4));
+    assert_eq!(set.get(&2), Some(&2));
+    assert_eq!(set.first(), Some(&1));
+    assert_eq!(set.last(), Some(&3));
+
+    assert!(set.remove(&1));
+    assert!(!set.remove(&1));
+    assert_eq!(set.take(&2), Some(2));
+    assert_eq!(set.take(&2), None);
+    assert_eq!(set.iter().copied().collect::<Vec<_>>(), vec![3]);
+    assert_eq!(set.pop_last(), Some(3));
+    assert!(set.is_empty());
+}
+
+#[test]
+fn replace_keeps_the_new_value() {
+    // compares only the first field
+    let mut set = RBTreeSet::with_comparator(|a: &(i32, &str), b: &(i32, &str)| a.0.cmp(&b.0));
+    assert_eq!(set.replace((1, "a")), None);
+    assert!(!set.insert((1, "b")));
+    assert_eq!(set.get(&(1, "")), Some(&(1, "a")));
+    assert_eq!(set.replace((1, "c")), Some((1, "a")));
+    assert_eq!(set.get(&(1, "")), Some(&(1, "c")));
+    assert_eq!(set.len(), 1);
+}
+
+#[test]
+fn range() {
+    let set: RBTreeSet<i32> = (0..10).collect();
+    assert_eq!(set.range(3..6).copied().collect::<Vec<_>>(), vec![3, 4, 5]);
+    assert_eq!(
+        set.range(..=2).rev().copied().collect::<Vec<_>>(),
+        vec![2, 1, 0]
+    );
+    assert_eq!(
+        set.range((Bound::Excluded(7), Bound::Unbounded))
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![8, 9]
+    );
+
+    let words: RBTreeSet<String> = ["b", "a", "c"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(
+        words
+            .range::<str, _>((Bound::Included("b"), Bound::Unbounded))
+            .count(),
+        2
+    );
+    assert!(words.contains("a"));
+    assert_eq!(format!("{:?}", words), r#"{"a", "b", "c"}"#);
+}
+
+#[test]
+fn set_operations() {
+    let a: RBTreeSet<i32> = [1, 2, 3, 5].into_iter().collect();
+    let b: RBTreeSet<i32> = [2, 4, 5, 6].into_iter().collect();
+    let v = |it: &mut dyn Iterator<Item = &i32>| it.copied().collect::<Vec<_>>();
+    assert_eq!(v(&mut a.union(&b)), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(v(&mut a.intersection(&b)), vec![2, 5]);
+    assert_eq!(v(&mut a.difference(&b)), vec![1, 3]);
+    assert_eq!(v(&mut a.symmetric_difference(&b)), vec![1, 3, 4, 6]);
+    assert!(!a.is_subset(&b));
+    assert!(!a.is_disjoint(&b));
+
+    let c: RBTreeSet<i32> = [2, 5].into_iter().collect();
+    assert!(c.is_subset(&a));
+    assert!(a.is_superset(&c));
+    assert!(c.is_disjoint(&[1, 3].into_iter().collect()));
+
+    // the iterators only walk as far as they are consumed
+    let big: RBTreeSet<i32> = (0..1000).collect();
+    let mut union = big.union(&a);
+    assert_eq!(union.next(), Some(&0));
+    assert_eq!(union.size_hint(), (999, Some(1003)));
+}
+
+#[quickcheck]
+fn set_operations_like_btreeset(a: Vec<u8>, b: Vec<u8>) -> bool {
+    let x: RBTreeSet<u8> = a.iter().copied().collect();
+    let y: RBTreeSet<u8> = b.iter().copied().collect();
+    let p: BTreeSet<u8> = a.into_iter().collect();
+    let q: BTreeSet<u8> = b.into_iter().collect();
+
+    x.iter().eq(p.iter())
+        && x.union(&y).eq(p.union(&q))
+        && x.intersection(&y).eq(p.intersection(&q))
+        && x.difference(&y).eq(p.difference(&q))
+        && x.symmetric_difference(&y).eq(p.symmetric_difference(&q))
+        && x.is_subset(&y) == p.is_subset(&q)
+        && x.is_superset(&y) == p.is_superset(&q)
+        && x.is_disjoint(&y) == p.is_disjoint(&q)
+        && x.clone().into_iter().rev().eq(p.into_iter().rev())
+}
+
+#[quickcheck]
+fn insert_and_remove_like_btreeset(ops: Vec<(bool, i8)>) -> bool {
+    let mut set = RBTreeSet::new();
+    let mut expected = BTreeSet::new();
+    for (insert, x) in ops {
+        let same = if insert {
+            set.insert(x) == expected.insert(x)
+        } else {
+            set.take(&x) == expected.take(&x)
+        };
+        if !same || set.first() != expected.first() || set.last() != expected.last() {
+            return false;
+        }
+    }
+    set.len() == expected.len() && set.iter().eq(expected.iter())
+}
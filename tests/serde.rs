@@ -0,0 +1,124 @@
+#![cfg(feature = "serde")]
+
+use dsgym_rs::avltree::AVLTree;
+use dsgym_rs::rbtree::RBTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+
+extern crate quickcheck;
+#[macro_use(quickcheck)]
+extern crate quickcheck_macros;
+
+#[derive(Default)]
+struct Descending;
+
+impl dsgym_rs::comparator::Comparator<i32> for Descending {
+    fn compare(&self, a: &i32, b: &i32) -> std::cmp::Ordering {
+        b.cmp(a)
+    }
+}
+
+#[test]
+fn rbtree_json() {
+    let map: RBTreeMap<String, i32> = [("b", 2), ("a", 1), ("c", 3)]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let json = serde_json::to_string(&map).unwrap();
+    assert_eq!(json, r#"{"a":1,"b":2,"c":3}"#);
+    assert_eq!(
+        serde_json::from_str::<RBTreeMap<String, i32>>(&json).unwrap(),
+        map
+    );
+
+    // unsorted input is accepted too
+    let map: RBTreeMap<String, i32> = serde_json::from_str(r#"{"c":3,"a":1,"b":2}"#).unwrap();
+    assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+
+    // and keys are ordered by the comparator of the map
+    let map: RBTreeMap<i32, (), Descending> =
+        serde_json::from_str(r#"{"1":null,"3":null,"2":null}"#).unwrap();
+    assert_eq!(map.keys().copied().collect::<Vec<_>>(), [3, 2, 1]);
+    assert_eq!(
+        serde_json::to_string(&map).unwrap(),
+        r#"{"3":null,"2":null,"1":null}"#
+    );
+}
+
+#[test]
+fn rbtree_duplicate_keys() {
+    let err = serde_json::from_str::<RBTreeMap<String, i32>>(r#"{"a":1,"b":2,"b":3}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("duplicate key at entry 2 of the map"),
+        "{}",
+        err
+    );
+    let err = serde_json::from_str::<RBTreeMap<String, i32>>(r#"{"b":1,"a":2,"b":3}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("duplicate key at entry 2 of the map"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn avltree_json() {
+    let mut tree = AVLTree::new();
+    for x in [3, 1, 2] {
+        tree.add(x);
+    }
+    let json = serde_json::to_string(&tree).unwrap();
+    assert_eq!(json, "[1,2,3]");
+
+    let tree: AVLTree<i32> = serde_json::from_str("[5,1,4,2,3]").unwrap();
+    assert_eq!(serde_json::to_string(&tree).unwrap(), "[1,2,3,4,5]");
+    assert!(tree.contains(&4));
+
+    let tree: AVLTree<i32, Descending> = serde_json::from_str("[1,2,3]").unwrap();
+    assert_eq!(serde_json::to_string(&tree).unwrap(), "[3,2,1]");
+}
+
+#[test]
+fn avltree_duplicate_keys() {
+    let err = serde_json::from_str::<AVLTree<i32>>("[1,2,2,3]")
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string()
+            .starts_with("duplicate key at element 2 of the sequence"),
+        "{}",
+        err
+    );
+    let err = serde_json::from_str::<AVLTree<i32>>("[3,1,0,1]")
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string()
+            .starts_with("duplicate key at element 3 of the sequence"),
+        "{}",
+        err
+    );
+}
+
+#[quickcheck]
+fn rbtree_bincode_round_trip(v: Vec<(i16, i16)>) -> bool {
+    let map: RBTreeMap<i16, i16> = v.iter().copied().collect();
+    let bytes = bincode::serialize(&map).unwrap();
+    let expected: BTreeMap<i16, i16> = v.into_iter().collect();
+    // the encoding is the same as the one of BTreeMap
+    bytes == bincode::serialize(&expected).unwrap()
+        && bincode::deserialize::<RBTreeMap<i16, i16>>(&bytes).unwrap() == map
+}
+
+#[quickcheck]
+fn avltree_bincode_round_trip(v: Vec<i16>) -> bool {
+    let mut tree = AVLTree::new();
+    for &x in v.iter() {
+        tree.add(x);
+    }
+    let bytes = bincode::serialize(&tree).unwrap();
+    let expected: BTreeSet<i16> = v.into_iter().collect();
+    let back: AVLTree<i16> = bincode::deserialize(&bytes).unwrap();
+    bytes == bincode::serialize(&expected).unwrap() && bincode::serialize(&back).unwrap() == bytes
+}